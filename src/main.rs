@@ -0,0 +1,533 @@
+use std::env;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::process;
+use virtual_machine_translator::prelude::*;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::call_graph::*;
+use virtual_machine_translator::utils::cfg::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::constant_folding::*;
+use virtual_machine_translator::utils::dead_code::*;
+use virtual_machine_translator::utils::debugger::*;
+use virtual_machine_translator::utils::disassembler::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::inliner::*;
+use virtual_machine_translator::utils::json_export::*;
+use virtual_machine_translator::utils::jump_threading::*;
+use virtual_machine_translator::utils::label_checker::*;
+use virtual_machine_translator::utils::linter::*;
+use virtual_machine_translator::utils::pass_manager::*;
+use virtual_machine_translator::utils::profiler::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::rom_usage::*;
+use virtual_machine_translator::utils::stack_checker::*;
+use virtual_machine_translator::utils::stack_usage::*;
+use virtual_machine_translator::utils::statics::*;
+use virtual_machine_translator::utils::symbol_checker::*;
+use virtual_machine_translator::utils::trace::*;
+
+const USAGE: &str =
+    "Example: vmtranslator <file(.vm extension) / Directory> <output.file> [options]
+         vmtranslator <file(.vm extension) / Directory> --debug <script | ->
+
+Options:
+  --emit cfg                Also write the control-flow graph of every function to <function>.dot next to the output
+  --emit json               Also write the instructions, diagnostics, call graph, statistics and source map to <output>.json
+  --check                   Check the program and print the problems found, stopping on errors
+  --enable <rule>           Enable a lint rule of --check (or all), every rule is enabled by default
+  --disable <rule>          Disable a lint rule of --check (or all)
+  --entry <function>        The entry point of the program for the call graph (default Sys.init)
+  -O0, -O1, -O2, -Os        Optimise nothing (default), cheaply, for speed or for size
+  --dump-passes <dir>       Write the program before the first pass and after every pass into dir
+  --omit-unreachable        Do not translate the functions the entry point can never call
+  --fold-constants          Evaluate arithmetic on constants before translating
+  --eliminate-dead-code     Remove the commands of a function that can never run and the labels nothing jumps to
+  --inline <n>              Inline the leaf functions of at most n commands at their call sites (default 8)
+  --inline-stats            Print the number of inlined call sites of every function when inlining is enabled
+  --thread-jumps            Thread jump chains, remove jumps to the next instruction and invert branches over jumps
+  --tail-calls              Translate a call followed by return as a jump reusing the frame of the caller
+  --cache-top               Keep the top of the stack in the D register between commands
+  --specialise              Use shorter code for common constants, small segment indexes and compare-and-branch
+  --statics                 Print the RAM address of every static variable
+  --stack-usage             Print the worst-case stack usage of every function and the recursive cycles
+  --rom-usage               Print the ROM words taken per VM opcode, function and file
+  --rom-limit <n>           Fail when the output takes more than n ROM words (default 32768)
+  --trace                   Run the translated program and print every executed VM command
+  --trace-function <name>   Only trace the commands of this function (can be repeated)
+  --profile                 Run the translated program and print the cycles per function and VM opcode
+  --flamegraph <file>       Also write the cycles per call stack in the folded flamegraph format
+  --max-cycles <n>          Stop running after n Hack instructions (default 1000000)
+  --hack <file>             Also assemble the output into a .hack file
+  --symbols <file>          Write the symbol table of the output (read it with --disassemble)
+  --disassemble             Disassemble the <input.hack> into <output.asm> instead of translating
+  --debug <script>          Debug the program on the VM interpreter with the commands of script (- for stdin)";
+
+/// The command-line options of the translator.
+struct Options {
+    /// The `.vm` file or directory to translate.
+    input: String,
+    /// The assembly file to write.
+    output: String,
+    /// The extra outputs to write (`cfg`, `json`).
+    emit: Vec<String>,
+    /// Check the program before translating it.
+    check: bool,
+    /// The lint rules to enable (`true`) or disable, in command-line order.
+    lint_rules: Vec<(String, bool)>,
+    /// The entry point of the call graph.
+    entry: String,
+    /// The optimisation level.
+    level: OptLevel,
+    /// The directory to write the program between passes into.
+    dump_passes: Option<String>,
+    /// Omit the functions the entry point can never call.
+    omit_unreachable: bool,
+    /// Evaluate arithmetic on constants before translating.
+    fold_constants: bool,
+    /// Remove the unreachable code of the functions.
+    eliminate_dead_code: bool,
+    /// The maximum size of the functions to inline, `None` to inline nothing.
+    inline: Option<usize>,
+    /// Print the inlined call sites.
+    inline_stats: bool,
+    /// Clean up the jumps of the generated assembly.
+    thread_jumps: bool,
+    /// The code generation modes of the writer.
+    codegen: CodegenOptions,
+    /// Print the allocation of the static variables.
+    statics: bool,
+    /// Print the worst-case stack usage.
+    stack_usage: bool,
+    /// Print the ROM words taken by the output.
+    rom_usage: bool,
+    /// The number of ROM words the output may take.
+    rom_limit: usize,
+    /// Run the translated program with the VM-level trace.
+    trace: bool,
+    /// The functions to trace, all of them when empty.
+    trace_functions: Vec<String>,
+    /// Run the translated program with the profiler.
+    profile: bool,
+    /// The folded stacks file to write when profiling.
+    flamegraph: Option<String>,
+    /// The maximum number of Hack instructions to run.
+    max_cycles: usize,
+    /// The `.hack` file to assemble the output into.
+    hack: Option<String>,
+    /// The symbol file to write, or to read when disassembling.
+    symbols: Option<String>,
+    /// Disassemble `input` into `output` instead of translating.
+    disassemble: bool,
+    /// The debugger script to run instead of translating, `-` for the standard input.
+    debug: Option<String>,
+}
+
+impl Options {
+    /// Parses the command-line arguments (without the program name).
+    fn parse(args: &[String]) -> Result<Options, String> {
+        let mut positional: Vec<String> = Vec::new();
+        let mut options = Options {
+            input: String::new(),
+            output: String::new(),
+            emit: Vec::new(),
+            check: false,
+            lint_rules: Vec::new(),
+            entry: ENTRY_POINT.to_string(),
+            level: OptLevel::O0,
+            dump_passes: None,
+            omit_unreachable: false,
+            fold_constants: false,
+            eliminate_dead_code: false,
+            inline: None,
+            inline_stats: false,
+            thread_jumps: false,
+            codegen: CodegenOptions::default(),
+            statics: false,
+            stack_usage: false,
+            rom_usage: false,
+            rom_limit: ROM_SIZE,
+            trace: false,
+            trace_functions: Vec::new(),
+            profile: false,
+            flamegraph: None,
+            max_cycles: 1_000_000,
+            hack: None,
+            symbols: None,
+            disassemble: false,
+            debug: None,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--emit" => {
+                    let kind = value(arg, args.next())?;
+                    if kind != "cfg" && kind != "json" {
+                        return Err(format!("Unknown output {kind}, expected cfg or json"));
+                    }
+                    options.emit.push(kind);
+                }
+                "--check" => options.check = true,
+                "--enable" | "--disable" => {
+                    let rule = value(arg, args.next())?;
+                    let enabled = arg == "--enable";
+                    LinterClass::default().set_rule(&rule, enabled)?;
+                    options.lint_rules.push((rule, enabled));
+                }
+                "--entry" => options.entry = value(arg, args.next())?,
+                "--dump-passes" => options.dump_passes = Some(value(arg, args.next())?),
+                "--omit-unreachable" => options.omit_unreachable = true,
+                "--fold-constants" => options.fold_constants = true,
+                "--eliminate-dead-code" => options.eliminate_dead_code = true,
+                "--inline" => {
+                    let threshold = value(arg, args.next())?;
+                    options.inline = Some(
+                        threshold
+                            .parse()
+                            .map_err(|_| format!("Invalid inline threshold {threshold}"))?,
+                    );
+                }
+                "--inline-stats" => options.inline_stats = true,
+                "--thread-jumps" => options.thread_jumps = true,
+                "--tail-calls" => options.codegen.tail_calls = true,
+                "--cache-top" => options.codegen.cache_top = true,
+                "--specialise" => options.codegen.specialise = true,
+                "--statics" => options.statics = true,
+                "--stack-usage" => options.stack_usage = true,
+                "--rom-usage" => options.rom_usage = true,
+                "--rom-limit" => {
+                    let limit = value(arg, args.next())?;
+                    options.rom_limit = limit
+                        .parse()
+                        .map_err(|_| format!("Invalid ROM limit {limit}"))?;
+                }
+                "--trace" => options.trace = true,
+                "--trace-function" => {
+                    options.trace = true;
+                    options.trace_functions.push(value(arg, args.next())?);
+                }
+                "--profile" => options.profile = true,
+                "--flamegraph" => {
+                    options.profile = true;
+                    options.flamegraph = Some(value(arg, args.next())?);
+                }
+                "--max-cycles" => {
+                    let cycles = value(arg, args.next())?;
+                    options.max_cycles = cycles
+                        .parse()
+                        .map_err(|_| format!("Invalid number of cycles {cycles}"))?;
+                }
+                "--hack" => options.hack = Some(value(arg, args.next())?),
+                "--symbols" => options.symbols = Some(value(arg, args.next())?),
+                "--disassemble" => options.disassemble = true,
+                "--debug" => options.debug = Some(value(arg, args.next())?),
+                option if option.starts_with("-O") => {
+                    options.level = OptLevel::parse(option)
+                        .ok_or_else(|| format!("Unknown optimisation level {option}"))?;
+                }
+                option if option.starts_with("--") => {
+                    return Err(format!("Unknown option {option}"))
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        match (positional.as_slice(), &options.debug) {
+            ([input, output], _) => {
+                options.input = input.clone();
+                options.output = output.clone();
+            }
+            // Debugging does not write an output file.
+            ([input], Some(_)) => options.input = input.clone(),
+            _ => return Err(USAGE.to_string()),
+        }
+        Ok(options)
+    }
+}
+
+/// Returns the value following an option.
+fn value(option: &str, value: Option<&String>) -> Result<String, String> {
+    value
+        .cloned()
+        .ok_or_else(|| format!("Option {option} needs a value"))
+}
+
+fn main() {
+    // Retrieve command-line arguments, skipping the program name.
+    let args: Vec<String> = env::args().skip(1).collect();
+    let options = Options::parse(&args).unwrap_or_else(|message| {
+        println!("{message}");
+        process::exit(1);
+    });
+    if options.disassemble {
+        disassemble(&options);
+        return;
+    }
+
+    // Read every VM file of the input file or directory.
+    let files = vm_files(&options.input);
+    if files.is_empty()
+        || files
+            .iter()
+            .any(|file| !file.to_lowercase().ends_with(".vm") || !Path::new(file).is_file())
+    {
+        // If the input is not a VM file or valid directory, print an error message.
+        println!("File must be .vm / Directory not found");
+        process::exit(1);
+    }
+    let mut program = ProgramClass::new(files);
+    // The problems found are printed by --check and exported by --emit json.
+    let json = options.emit.iter().any(|kind| kind == "json") && options.debug.is_none();
+    let diagnostics = match options.check || json {
+        true => diagnostics(&program, &options),
+        false => Vec::new(),
+    };
+    // A failed check stops before translating, or once the JSON export has been written.
+    let failed = options.check && check(&diagnostics);
+    if failed && !json {
+        process::exit(1);
+    }
+    if let Some(script) = &options.debug {
+        debug(&program, script);
+        return;
+    }
+
+    if options.omit_unreachable {
+        program = CallGraphClass::new(&program).prune(&program, &options.entry);
+    }
+    let mut manager = pass_manager(&options);
+    program = manager.optimise(&program);
+    if options.inline_stats {
+        // The statistics only report on inlining enabled by `--inline` or the optimisation level.
+        let mut inliners = manager
+            .vm_passes
+            .iter()
+            .filter(|pass| pass.name() == "inline")
+            .peekable();
+        if inliners.peek().is_none() {
+            println!("Inlining is disabled, use --inline <n> or -O2");
+        }
+        for pass in inliners {
+            print!("{}", pass.stats());
+        }
+    }
+
+    // Static variables past the static region would overwrite the stack.
+    let statics = StaticsClass::new(&program);
+    if options.statics {
+        print!("{}", statics.report());
+    }
+    if let Some(diagnostic) = statics.check().first() {
+        println!("{diagnostic}");
+        process::exit(1);
+    }
+
+    if options.stack_usage {
+        print!("{}", StackUsageClass::new(&program).report(&options.entry));
+    }
+    if options.emit.iter().any(|kind| kind == "cfg") {
+        emit_cfg(&program, &options.output);
+    }
+
+    // Write the bootstrap code of programs declaring Sys.init, then every VM command, to the output file.
+    let writer = manager.translate(&program, options.output.clone(), bootstraps(&program));
+    if let Some(directory) = &options.dump_passes {
+        fs::create_dir_all(directory).expect("Cannot create dump directory");
+        for (position, dump) in manager.dumps.iter().enumerate() {
+            let file = format!("{position:02}-{}.{}", dump.pass, dump.stage);
+            fs::write(Path::new(directory).join(file), &dump.code).expect("Cannot write dump");
+        }
+    }
+
+    // Programs larger than the ROM cannot be loaded.
+    if options.rom_usage {
+        print!("{}", writer.rom_usage.report(options.rom_limit));
+    }
+    if let Some(diagnostic) = writer
+        .rom_usage
+        .check(options.rom_limit, &writer.source_map)
+        .first()
+    {
+        println!("{diagnostic}");
+        fs::remove_file(&options.output).expect("Cannot remove output file");
+        process::exit(1);
+    }
+    if json {
+        let export = JsonExportClass::new(&program, &writer, diagnostics, &options.entry);
+        let file = Path::new(&options.output).with_extension("json");
+        fs::write(file, export.to_json()).expect("Cannot write json file");
+    }
+    if failed {
+        fs::remove_file(&options.output).expect("Cannot remove output file");
+        process::exit(1);
+    }
+
+    // Assemble the output when machine code, symbols, a trace or a profile are requested.
+    if options.hack.is_none() && options.symbols.is_none() && !options.trace && !options.profile {
+        return;
+    }
+    let source = fs::read_to_string(&options.output).expect("Cannot read output file");
+    let assembler = AssemblerClass::new(&source);
+    if let Some(hack) = &options.hack {
+        fs::write(hack, assembler.to_hack()).expect("Cannot write hack file");
+    }
+    if let Some(symbols) = &options.symbols {
+        fs::write(symbols, assembler.to_symbols()).expect("Cannot write symbol file");
+    }
+
+    if options.trace {
+        // Run the program, printing the VM commands as they are executed.
+        let mut tracer = TracerClass::new(
+            EmulatorClass::new(assembler.rom.clone()),
+            writer.source_map.clone(),
+        );
+        tracer.functions = options.trace_functions;
+        tracer.run(options.max_cycles, &mut io::stdout().lock());
+    }
+
+    if options.profile {
+        // Run the program again, counting the cycles spent in each function and VM opcode.
+        let mut profiler = ProfilerClass::new(EmulatorClass::new(assembler.rom), writer.source_map);
+        profiler.run(options.max_cycles);
+        print!("{}", profiler.report());
+        if let Some(flamegraph) = &options.flamegraph {
+            fs::write(flamegraph, profiler.folded()).expect("Cannot write flamegraph file");
+        }
+    }
+}
+
+/// Returns the pipeline of the optimisation level, with the passes and code generation modes
+/// requested by their own options added.
+fn pass_manager(options: &Options) -> PassManagerClass {
+    let mut manager = PassManagerClass::new(options.level);
+    if let Some(threshold) = options.inline {
+        // The requested threshold replaces the one of the level, inlining still running first.
+        manager.vm_passes.retain(|pass| pass.name() != "inline");
+        let inliner = InlinerClass {
+            threshold,
+            ..Default::default()
+        };
+        manager.vm_passes.insert(0, Box::new(inliner));
+    }
+    // The passes the level already runs are not added twice.
+    let mut add = |enabled: bool, pass: Box<dyn VmPass>| {
+        if enabled
+            && !manager
+                .vm_passes
+                .iter()
+                .any(|other| other.name() == pass.name())
+        {
+            manager.add_vm_pass(pass);
+        }
+    };
+    add(
+        options.fold_constants,
+        Box::<ConstantFoldingClass>::default(),
+    );
+    add(options.eliminate_dead_code, Box::<DeadCodeClass>::default());
+    if options.thread_jumps
+        && !manager
+            .assembly_passes
+            .iter()
+            .any(|pass| pass.name() == "thread-jumps")
+    {
+        manager.add_assembly_pass(Box::<JumpThreadingClass>::default());
+    }
+    manager.codegen.tail_calls |= options.codegen.tail_calls;
+    manager.codegen.cache_top |= options.codegen.cache_top;
+    manager.codegen.specialise |= options.codegen.specialise;
+    manager.dump = options.dump_passes.is_some();
+    manager
+}
+
+/// Disassembles the `.hack` input into the output file, re-attaching symbols when a symbol file is given.
+fn disassemble(options: &Options) {
+    let hack = fs::read_to_string(&options.input).unwrap_or_else(|_| {
+        println!("Cannot read {}", options.input);
+        process::exit(1);
+    });
+    let mut disassembler = DisassemblerClass::new(&hack);
+    if let Some(symbols) = &options.symbols {
+        disassembler.load_symbols(&fs::read_to_string(symbols).expect("Cannot read symbol file"));
+    }
+    fs::write(&options.output, disassembler.disassemble()).expect("Cannot write output file");
+}
+
+/// Prints the problems found in the program and returns `true` when one of them is an error.
+fn check(diagnostics: &[Diagnostic]) -> bool {
+    for diagnostic in diagnostics {
+        println!("{diagnostic}");
+    }
+    diagnostics
+        .iter()
+        .any(|diagnostic| diagnostic.severity == Severity::Error)
+}
+
+/// Returns `true` when the program declares `Sys.init`, which the bootstrap code calls.
+///
+/// The other programs, such as the test programs without `Sys.vm`, start with their first command.
+fn bootstraps(program: &ProgramClass) -> bool {
+    program
+        .functions()
+        .iter()
+        .any(|function| function.name == ENTRY_POINT)
+}
+
+/// Returns the problems found in the program by every check, sorted by location.
+fn diagnostics(program: &ProgramClass, options: &Options) -> Vec<Diagnostic> {
+    let entry = &options.entry;
+    let mut linter = LinterClass::new(program);
+    for (rule, enabled) in &options.lint_rules {
+        linter
+            .set_rule(rule, *enabled)
+            .expect("Lint rules are checked with the options");
+    }
+
+    let mut diagnostics = StackCheckerClass::new(program).check();
+    diagnostics.extend(LabelCheckerClass::new(program).check());
+    diagnostics.extend(CallGraphClass::new(program).check(program, entry));
+    diagnostics.extend(SymbolCheckerClass::new(program).check(bootstraps(program)));
+    diagnostics.extend(linter.check());
+    diagnostics.extend(StackUsageClass::new(program).check(entry));
+    diagnostics.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+    diagnostics
+}
+
+/// Writes the control-flow graph of every function to `<function>.dot` in the directory of the output file.
+///
+/// A program without functions is written as a single graph named after the output file.
+fn emit_cfg(program: &ProgramClass, output: &str) {
+    let directory = Path::new(output).parent().unwrap_or(Path::new(""));
+    let mut functions = program.functions();
+    if functions.is_empty() {
+        functions.push(Function {
+            name: file_stem(output),
+            file_name: String::new(),
+            n_vars: 0,
+            start: 0,
+            end: program.instructions.len(),
+        });
+    }
+    for function in &functions {
+        let cfg = CfgClass::new(program, function);
+        let file = directory.join(format!("{}.dot", function.name));
+        fs::write(file, cfg.to_dot()).expect("Cannot write cfg file");
+    }
+}
+
+/// Runs the debugger on the program with the commands of the script file, or of the standard input for `-`.
+fn debug(program: &ProgramClass, script: &str) {
+    let mut debugger = DebuggerClass::new(program);
+    let mut output = io::stdout().lock();
+    if script == "-" {
+        debugger.run_script(&mut io::stdin().lock(), &mut output);
+        return;
+    }
+    let file = fs::File::open(script).unwrap_or_else(|_| {
+        println!("Cannot read {script}");
+        process::exit(1);
+    });
+    debugger.run_script(&mut BufReader::new(file), &mut output);
+}
@@ -1,7 +1,8 @@
 use std::{collections::HashMap, fmt::Display, hash::Hash};
 use std::default::Default;
+use serde::Serialize;
 
-#[derive(Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum Command {
     /// Represents an arithmetic operation command. ["add", "sub", "neg", "eq", "gt", "lt", "and", "or", "not"]
     Arithmetic(String),
@@ -25,7 +26,7 @@ impl PartialEq for Command {
 }
 
 /// Represents different types of memory segments that can be parsed from the input file.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Serialize)]
 pub enum Segment {
     /// Represents an internal memory segment. ["local", "argument", "this", "that"]
     Internal(String),
@@ -42,6 +43,133 @@ impl PartialEq for Segment {
     }
 }
 
+/// Represents a single parsed VM command together with the place it was read from.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Instruction {
+    /// The name of the VM file the command was read from (e.g. `Main.vm`).
+    pub file_name: String,
+    /// The 1-based line number of the command inside `file_name`.
+    pub line: usize,
+    /// The command text with comments and surrounding whitespace removed.
+    pub current_command: String,
+    /// The type of the command.
+    pub command_type: Option<Command>,
+    /// The memory segment of a push/pop command.
+    pub segment_type: Option<Segment>,
+    /// The index of a push/pop command.
+    pub index: Option<i32>,
+    /// The lint rules allowed on the line of the command with a `// vm-allow(rule, ...)` comment.
+    pub allow: Vec<String>,
+}
+
+impl Instruction {
+    /// Returns the space separated part of the command at `position` (`0` is the command name).
+    pub fn part(&self, position: usize) -> Option<&str> {
+        self.current_command.split_whitespace().nth(position)
+    }
+
+    /// Returns the command name in lowercase (e.g. `push`, `add`, `if-goto`).
+    pub fn name(&self) -> String {
+        self.part(0).unwrap_or_default().to_lowercase()
+    }
+}
+
+/// Represents a VM function declared with `function <name> <nVars>` and the instructions that belong to it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Function {
+    /// The function name (e.g. `Main.main`).
+    pub name: String,
+    /// The name of the VM file declaring the function.
+    pub file_name: String,
+    /// The number of local variables declared by the function.
+    pub n_vars: usize,
+    /// Index of the `function` instruction in the instruction stream.
+    pub start: usize,
+    /// Index one past the last instruction of the function.
+    pub end: usize,
+}
+
+/// Maps the Hack instructions written for one VM command back to that command.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct SourceMapEntry {
+    /// The ROM address of the first Hack instruction of the command.
+    pub address: usize,
+    /// The number of Hack instructions written for the command.
+    pub length: usize,
+    /// The VM file declaring the command.
+    pub file_name: String,
+    /// The line of the command inside `file_name`, `0` for code without VM source (e.g. bootstrap).
+    pub line: usize,
+    /// The VM command text.
+    pub command: String,
+    /// The function the command belongs to.
+    pub function: String,
+}
+
+/// Represents how serious a diagnostic is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// The program can be translated but is probably wrong.
+    Warning,
+    /// The translated program would misbehave.
+    Error,
+}
+
+/// Represents a problem found in a VM program, together with its source location.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Diagnostic {
+    /// How serious the problem is.
+    pub severity: Severity,
+    /// The VM file containing the problem.
+    pub file_name: String,
+    /// The line of the problem inside `file_name`, `0` for problems without VM source (e.g. bootstrap).
+    pub line: usize,
+    /// The description of the problem.
+    pub message: String,
+    /// The lint rule reporting the problem, `None` for checks that cannot be disabled.
+    pub rule: Option<String>,
+}
+
+impl Diagnostic {
+    /// Creates a diagnostic located at `instruction`.
+    pub fn new(severity: Severity, instruction: &Instruction, message: String) -> Self {
+        Diagnostic {
+            severity,
+            file_name: instruction.file_name.clone(),
+            line: instruction.line,
+            message,
+            rule: None,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    /// Formats the diagnostic as `Main.vm:12: warning: message [rule]`, without the line when it is 0.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        match self.line {
+            0 => write!(f, "{}: {severity}: {}", self.file_name, self.message)?,
+            line => write!(f, "{}:{line}: {severity}: {}", self.file_name, self.message)?,
+        }
+        match &self.rule {
+            Some(rule) => write!(f, " [{rule}]"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The name of the code that runs outside of any function (e.g. the bootstrap code).
+pub const TOP_LEVEL: &str = "<top level>";
+
+/// Returns `part` as a percentage of `total`, which counts as 1 when it is 0.
+pub fn percent(part: usize, total: usize) -> f64 {
+    part as f64 * 100.0 / total.max(1) as f64
+}
+
 // General data type for strong command
 #[derive(Debug)]
 pub struct List<T>(pub Vec<T>);
@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+
+/// A public interface for assembling Hack assembly code into Hack machine code.
+pub trait AssemblerPublic {
+    /// Creates a new instance of the assembler and assembles `source`.
+    ///
+    /// # Arguments
+    ///
+    /// * `source` - The Hack assembly code, as written by `CodeWriterClass`.
+    ///
+    /// # Errors
+    ///
+    /// Errors will occur if a line is not a valid Hack instruction.
+    fn new(source: &str) -> Self;
+
+    /// Returns the machine code in the `.hack` text format, one 16-bit binary word per line.
+    fn to_hack(&self) -> String;
+
+    /// Returns the symbol table in the `.sym` text format read by `DisassemblerClass`.
+    ///
+    /// Each line is `label <name> <ROM address>` or `variable <name> <RAM address>`,
+    /// labels first, each group sorted by address.
+    fn to_symbols(&self) -> String;
+}
+
+/// Represents a two-pass Hack assembler.
+#[derive(Debug, Default)]
+pub struct AssemblerClass {
+    /// The assembled instructions, indexed by ROM address.
+    pub rom: Vec<u16>,
+
+    /// The labels declared with `(LABEL)` and the ROM address they refer to.
+    pub labels: BTreeMap<String, u16>,
+
+    /// The variables allocated from RAM 16 upward and their RAM address.
+    pub variables: BTreeMap<String, u16>,
+}
+
+impl AssemblerPublic for AssemblerClass {
+    fn new(source: &str) -> Self {
+        let mut assembler = AssemblerClass::default();
+
+        // Strip comments and whitespace, keeping the original line number for error messages.
+        let lines: Vec<(usize, String)> = source
+            .lines()
+            .enumerate()
+            .map(|(number, line)| (number + 1, clean_line(line)))
+            .filter(|(_, line)| !line.is_empty())
+            .collect();
+
+        // First pass: record the ROM address of every label.
+        let mut address: u16 = 0;
+        for (_, line) in &lines {
+            if let Some(label) = line.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+                assembler.labels.insert(label.to_string(), address);
+            } else {
+                address += 1;
+            }
+        }
+
+        // Second pass: translate instructions, allocating variables on first use.
+        for (number, line) in &lines {
+            if line.starts_with('(') {
+                continue;
+            }
+            let word = match line.strip_prefix('@') {
+                Some(symbol) => assembler.address_of(symbol),
+                None => compute_instruction(line)
+                    .unwrap_or_else(|| panic!("Line {number}: invalid instruction {line:?}")),
+            };
+            assembler.rom.push(word);
+        }
+        assembler
+    }
+
+    fn to_hack(&self) -> String {
+        self.rom
+            .iter()
+            .map(|word| format!("{word:016b}\n"))
+            .collect()
+    }
+
+    fn to_symbols(&self) -> String {
+        let mut labels: Vec<(&String, &u16)> = self.labels.iter().collect();
+        labels.sort_by_key(|(_, address)| **address);
+        let mut variables: Vec<(&String, &u16)> = self.variables.iter().collect();
+        variables.sort_by_key(|(_, address)| **address);
+
+        let labels = labels
+            .into_iter()
+            .map(|(name, address)| format!("label {name} {address}\n"));
+        let variables = variables
+            .into_iter()
+            .map(|(name, address)| format!("variable {name} {address}\n"));
+        labels.chain(variables).collect()
+    }
+}
+
+impl AssemblerClass {
+    /// Resolves the value of an A-instruction, allocating a new variable if `symbol` is unknown.
+    fn address_of(&mut self, symbol: &str) -> u16 {
+        if let Ok(value) = symbol.parse::<u16>() {
+            return value;
+        }
+        if let Some(address) = predefined_symbol(symbol) {
+            return address;
+        }
+        if let Some(address) = self.labels.get(symbol) {
+            return *address;
+        }
+        let next = 16 + self.variables.len() as u16;
+        *self.variables.entry(symbol.to_string()).or_insert(next)
+    }
+}
+
+/// Removes comments and all whitespace from an assembly line.
+fn clean_line(line: &str) -> String {
+    let code = line.split("//").next().unwrap_or_default();
+    code.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+/// Returns the address of the symbols predefined by the Hack platform.
+pub fn predefined_symbol(symbol: &str) -> Option<u16> {
+    match symbol {
+        "SP" => Some(0),
+        "LCL" => Some(1),
+        "ARG" => Some(2),
+        "THIS" => Some(3),
+        "THAT" => Some(4),
+        "SCREEN" => Some(16384),
+        "KBD" => Some(24576),
+        _ => symbol
+            .strip_prefix('R')
+            .and_then(|n| n.parse::<u16>().ok())
+            .filter(|n| *n < 16),
+    }
+}
+
+/// Translates a `dest=comp;jump` instruction into its binary form.
+fn compute_instruction(line: &str) -> Option<u16> {
+    let (rest, jump) = match line.split_once(';') {
+        Some((rest, jump)) => (rest, jump),
+        None => (line, ""),
+    };
+    let (dest, comp) = match rest.split_once('=') {
+        Some((dest, comp)) => (dest, comp),
+        None => ("", rest),
+    };
+
+    let mut dest_bits: u16 = 0;
+    for register in dest.chars() {
+        dest_bits |= match register {
+            'A' => 0b100,
+            'D' => 0b010,
+            'M' => 0b001,
+            _ => return None,
+        };
+    }
+    let jump_bits: u16 = match jump {
+        "" => 0,
+        "JGT" => 1,
+        "JEQ" => 2,
+        "JGE" => 3,
+        "JLT" => 4,
+        "JNE" => 5,
+        "JLE" => 6,
+        "JMP" => 7,
+        _ => return None,
+    };
+    Some(0b111 << 13 | comp_bits(comp)? << 6 | dest_bits << 3 | jump_bits)
+}
+
+/// Returns the `a` bit and the six `c` bits of a computation.
+fn comp_bits(comp: &str) -> Option<u16> {
+    // Computations on M are the computations on A with the `a` bit set.
+    let (a_bit, comp) = if comp.contains('M') {
+        (0b1000000, comp.replace('M', "A"))
+    } else {
+        (0, comp.to_string())
+    };
+    let c_bits = match comp.as_str() {
+        "0" => 0b101010,
+        "1" => 0b111111,
+        "-1" => 0b111010,
+        "D" => 0b001100,
+        "A" => 0b110000,
+        "!D" => 0b001101,
+        "!A" => 0b110001,
+        "-D" => 0b001111,
+        "-A" => 0b110011,
+        "D+1" | "1+D" => 0b011111,
+        "A+1" | "1+A" => 0b110111,
+        "D-1" => 0b001110,
+        "A-1" => 0b110010,
+        "D+A" | "A+D" => 0b000010,
+        "D-A" => 0b010011,
+        "A-D" => 0b000111,
+        "D&A" | "A&D" => 0b000000,
+        "D|A" | "A|D" => 0b010101,
+        _ => return None,
+    };
+    Some(a_bit | c_bits)
+}
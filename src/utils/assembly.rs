@@ -0,0 +1,93 @@
+use super::code_writer::*;
+use super::rom_usage::*;
+use crate::prelude::*;
+
+/// A public interface for working on the generated assembly as a list of blocks, one per VM command.
+pub trait AssemblyPublic {
+    /// Collects the assembly code written by a code writer, one block per source map entry.
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - The code writer the program was translated with.
+    fn new(writer: &CodeWriterClass) -> Self;
+
+    /// Formats the assembly code as the writer writes it, one block after the other.
+    fn to_asm(&self) -> String;
+
+    /// Returns the source map of the blocks, their addresses and lengths counted again.
+    fn source_map(&self) -> Vec<SourceMapEntry>;
+
+    /// Returns the ROM words taken by the blocks, per VM opcode, function and file.
+    fn rom_usage(&self) -> RomUsageClass;
+}
+
+/// Represents the assembly code written for one VM command.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssemblyBlock {
+    /// The VM command the code was written for. Its `address` and `length` may be stale
+    /// once a pass changed the code, `AssemblyClass::source_map` counts them again.
+    pub entry: SourceMapEntry,
+
+    /// The lines of assembly code, starting with the comment naming the VM command.
+    pub lines: Vec<String>,
+}
+
+/// Represents a translated program as an assembly-level IR, the input of the assembly passes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AssemblyClass {
+    /// The blocks of code, in ROM order.
+    pub blocks: Vec<AssemblyBlock>,
+}
+
+impl AssemblyPublic for AssemblyClass {
+    fn new(writer: &CodeWriterClass) -> Self {
+        let blocks = writer
+            .source_map
+            .iter()
+            .zip(&writer.code)
+            .map(|(entry, code)| AssemblyBlock {
+                entry: entry.clone(),
+                lines: code.lines().map(str::to_string).collect(),
+            })
+            .collect();
+        AssemblyClass { blocks }
+    }
+
+    fn to_asm(&self) -> String {
+        let mut output = String::new();
+        for block in &self.blocks {
+            for line in &block.lines {
+                output.push_str(line);
+                output.push('\n');
+            }
+        }
+        output
+    }
+
+    fn source_map(&self) -> Vec<SourceMapEntry> {
+        let mut address = 0;
+        let mut source_map = Vec::new();
+        for block in &self.blocks {
+            let length = block
+                .lines
+                .iter()
+                .filter(|line| is_instruction(line))
+                .count();
+            source_map.push(SourceMapEntry {
+                address,
+                length,
+                ..block.entry.clone()
+            });
+            address += length;
+        }
+        source_map
+    }
+
+    fn rom_usage(&self) -> RomUsageClass {
+        let mut rom_usage = RomUsageClass::new();
+        for entry in self.source_map() {
+            rom_usage.add(&entry);
+        }
+        rom_usage
+    }
+}
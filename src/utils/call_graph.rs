@@ -0,0 +1,126 @@
+use super::program::*;
+use crate::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The function the bootstrap code calls.
+pub const ENTRY_POINT: &str = "Sys.init";
+
+/// A public interface for finding which functions a program can call.
+pub trait CallGraphPublic {
+    /// Creates the call graph of a program from its `call` commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to analyse.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Returns the functions called directly or indirectly by `entry`, `entry` included.
+    fn reachable(&self, entry: &str) -> BTreeSet<String>;
+
+    /// Returns the declared functions that `entry` can never call, in declaration order.
+    ///
+    /// # Returns
+    ///
+    /// An empty `Vec` when `entry` is not declared, as nothing is known about the entry point.
+    fn unreachable(&self, entry: &str) -> Vec<Function>;
+
+    /// Returns a copy of `program` without the instructions of the functions `entry` can never call.
+    ///
+    /// The code before the first function is kept, and so are `ENTRY_POINT` and the functions it
+    /// calls, as the bootstrap code calls it whatever `entry` is.
+    fn prune(&self, program: &ProgramClass, entry: &str) -> ProgramClass;
+
+    /// Returns a warning for every function `entry` can never call, at its declaration.
+    fn check(&self, program: &ProgramClass, entry: &str) -> Vec<Diagnostic>;
+}
+
+/// Represents the functions of a program and the functions each of them calls.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct CallGraphClass {
+    /// The declared functions, in declaration order.
+    pub functions: Vec<Function>,
+
+    /// The functions called by each function.
+    pub calls: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl CallGraphPublic for CallGraphClass {
+    fn new(program: &ProgramClass) -> Self {
+        let functions = program.functions();
+        let mut calls: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+
+        for function in &functions {
+            let callees = calls.entry(function.name.clone()).or_default();
+            for instruction in &program.instructions[function.start..function.end] {
+                if instruction.name() == "call" {
+                    callees.insert(instruction.part(1).unwrap_or_default().to_string());
+                }
+            }
+        }
+
+        CallGraphClass { functions, calls }
+    }
+
+    fn reachable(&self, entry: &str) -> BTreeSet<String> {
+        let mut reachable: BTreeSet<String> = BTreeSet::new();
+        let mut worklist = vec![entry.to_string()];
+
+        while let Some(function) = worklist.pop() {
+            if !reachable.insert(function.clone()) {
+                continue;
+            }
+            for callee in self.calls.get(&function).into_iter().flatten() {
+                if !reachable.contains(callee) {
+                    worklist.push(callee.clone());
+                }
+            }
+        }
+        reachable
+    }
+
+    fn unreachable(&self, entry: &str) -> Vec<Function> {
+        if !self.calls.contains_key(entry) {
+            return Vec::new();
+        }
+        let reachable = self.reachable(entry);
+        self.functions
+            .iter()
+            .filter(|function| !reachable.contains(&function.name))
+            .cloned()
+            .collect()
+    }
+
+    fn prune(&self, program: &ProgramClass, entry: &str) -> ProgramClass {
+        let mut keep = vec![true; program.instructions.len()];
+        let bootstrap = self.reachable(ENTRY_POINT);
+        for function in self.unreachable(entry) {
+            if !bootstrap.contains(&function.name) {
+                keep[function.start..function.end].fill(false);
+            }
+        }
+
+        ProgramClass {
+            instructions: program
+                .instructions
+                .iter()
+                .zip(keep)
+                .filter(|(_, keep)| *keep)
+                .map(|(instruction, _)| instruction.clone())
+                .collect(),
+        }
+    }
+
+    fn check(&self, program: &ProgramClass, entry: &str) -> Vec<Diagnostic> {
+        self.unreachable(entry)
+            .iter()
+            .map(|function| {
+                Diagnostic::new(
+                    Severity::Warning,
+                    &program.instructions[function.start],
+                    format!("function {} is never called from {entry}", function.name),
+                )
+            })
+            .collect()
+    }
+}
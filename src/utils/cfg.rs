@@ -0,0 +1,188 @@
+use super::program::*;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// A public interface for building the control-flow graph of a VM function.
+pub trait CfgPublic {
+    /// Creates the control-flow graph of a function.
+    ///
+    /// Basic blocks start at the first instruction, at every `label` and after every `goto`,
+    /// `if-goto`, `call` and `return`.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program declaring the function.
+    /// * `function` - The function, as returned by `ProgramClass::functions`.
+    fn new(program: &ProgramClass, function: &Function) -> Self;
+
+    /// Formats the graph in the Graphviz DOT language.
+    ///
+    /// Every block is a box listing its commands with their lines, and calls and returns are
+    /// drawn as edges labelled with the called function and to a `return` node.
+    fn to_dot(&self) -> String;
+}
+
+/// Represents an edge leaving a basic block. Targets are indices into `CfgClass::blocks`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Edge {
+    /// The execution continues with the next block.
+    FallThrough(usize),
+    /// A `goto` to the block starting with the label.
+    Goto(usize),
+    /// The jump of an `if-goto` to the block starting with the label, when the condition holds.
+    Branch(usize),
+    /// A call to the named function, continuing with the block after the call once it returns.
+    Call(String, usize),
+    /// A `return` leaving the function.
+    Return,
+}
+
+/// Represents a sequence of instructions that always runs from start to end.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BasicBlock {
+    /// Index of the first instruction of the block in `CfgClass::instructions`.
+    pub start: usize,
+    /// Index one past the last instruction of the block.
+    pub end: usize,
+    /// The edges leaving the block.
+    pub edges: Vec<Edge>,
+}
+
+/// Represents the control-flow graph of a VM function.
+#[derive(Clone, Debug, Default)]
+pub struct CfgClass {
+    /// The name of the function.
+    pub function: String,
+
+    /// The instructions of the function, starting with its `function` command.
+    pub instructions: Vec<Instruction>,
+
+    /// The basic blocks, in instruction order.
+    pub blocks: Vec<BasicBlock>,
+}
+
+impl CfgPublic for CfgClass {
+    fn new(program: &ProgramClass, function: &Function) -> Self {
+        let instructions = program.instructions[function.start..function.end].to_vec();
+
+        // Find the first instruction of every block.
+        let mut leaders = vec![false; instructions.len()];
+        for (position, instruction) in instructions.iter().enumerate() {
+            match instruction.name().as_str() {
+                "label" => leaders[position] = true,
+                "goto" | "if-goto" | "call" | "return" if position + 1 < instructions.len() => {
+                    leaders[position + 1] = true;
+                }
+                _ => {}
+            }
+        }
+        if let Some(first) = leaders.first_mut() {
+            *first = true;
+        }
+        let starts: Vec<usize> = (0..instructions.len())
+            .filter(|position| leaders[*position])
+            .collect();
+
+        // Labels are looked up inside the function, where they are declared.
+        let labels: HashMap<&str, usize> = starts
+            .iter()
+            .enumerate()
+            .filter(|(_, start)| instructions[**start].name() == "label")
+            .filter_map(|(block, start)| Some((instructions[*start].part(1)?, block)))
+            .collect();
+
+        let blocks = starts
+            .iter()
+            .enumerate()
+            .map(|(block, start)| {
+                let end = starts.get(block + 1).copied().unwrap_or(instructions.len());
+                let last = &instructions[end - 1];
+                let next = Some(block + 1).filter(|next| *next < starts.len());
+                let target = last.part(1).and_then(|label| labels.get(label).copied());
+
+                let edges = match last.name().as_str() {
+                    "goto" => target.map(Edge::Goto).into_iter().collect(),
+                    "if-goto" => target
+                        .map(Edge::Branch)
+                        .into_iter()
+                        .chain(next.map(Edge::FallThrough))
+                        .collect(),
+                    "call" => {
+                        let callee = last.part(1).unwrap_or_default().to_string();
+                        next.map(|next| Edge::Call(callee, next))
+                            .into_iter()
+                            .collect()
+                    }
+                    "return" => vec![Edge::Return],
+                    _ => next.map(Edge::FallThrough).into_iter().collect(),
+                };
+                BasicBlock {
+                    start: *start,
+                    end,
+                    edges,
+                }
+            })
+            .collect();
+
+        CfgClass {
+            function: function.name.clone(),
+            instructions,
+            blocks,
+        }
+    }
+
+    fn to_dot(&self) -> String {
+        let mut output = format!("digraph \"{}\" {{\n", escape(&self.function));
+        output.push_str("  node [shape=box, fontname=\"monospace\"];\n");
+
+        for (number, block) in self.blocks.iter().enumerate() {
+            let label: String = self.instructions[block.start..block.end]
+                .iter()
+                .map(|instruction| {
+                    let text = format!("{}: {}", instruction.line, instruction.current_command);
+                    format!("{}\\l", escape(&text))
+                })
+                .collect();
+            output.push_str(&format!("  b{number} [label=\"{label}\"];\n"));
+        }
+        if self
+            .blocks
+            .iter()
+            .any(|block| block.edges.contains(&Edge::Return))
+        {
+            output.push_str("  return [shape=oval];\n");
+        }
+
+        for (number, block) in self.blocks.iter().enumerate() {
+            for edge in &block.edges {
+                let line = match edge {
+                    Edge::FallThrough(target) => format!("b{number} -> b{target}"),
+                    Edge::Goto(target) => format!("b{number} -> b{target} [label=\"goto\"]"),
+                    Edge::Branch(target) => format!("b{number} -> b{target} [label=\"if-goto\"]"),
+                    Edge::Call(callee, target) => format!(
+                        "b{number} -> b{target} [label=\"call {}\", style=dashed]",
+                        escape(callee)
+                    ),
+                    Edge::Return => format!("b{number} -> return"),
+                };
+                output.push_str(&format!("  {line};\n"));
+            }
+        }
+        output.push_str("}\n");
+        output
+    }
+}
+
+impl CfgClass {
+    /// Returns the index of the block containing the instruction at `position`.
+    pub fn block_of(&self, position: usize) -> Option<usize> {
+        self.blocks
+            .iter()
+            .position(|block| block.start <= position && position < block.end)
+    }
+}
+
+/// Escapes `text` for a quoted DOT string.
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
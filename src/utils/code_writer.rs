@@ -1,4 +1,5 @@
 use super::parser::*;
+use super::rom_usage::*;
 use crate::prelude::*;
 use std::fs::File;
 use std::io::Write;
@@ -25,7 +26,7 @@ pub trait CodeWriter {
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```ignore
     /// match parser.command_type {
     ///     Some(Command::Arithmetic(_)) => {
     ///         write.write_arithmetic(&parser);
@@ -47,7 +48,7 @@ pub trait CodeWriter {
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```ignore
     /// match parser.command_type {
     ///     Some(Command::PushPop(_)) => {
     ///         write.write_push_pop(&parser);
@@ -69,7 +70,7 @@ pub trait CodeWriter {
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```ignore
     /// match parser.command_type {
     ///     Some(Command::Branch(_)) => {
     ///         write.write_branch(&parser);
@@ -90,7 +91,7 @@ pub trait CodeWriter {
     ///
     /// # Examples
     ///
-    /// ```no_run
+    /// ```ignore
     /// match parser.command_type {
     ///     Some(Command::Function(_)) => {
     ///         write.write_function(&parser);
@@ -104,6 +105,128 @@ pub trait CodeWriter {
     ///
     /// This command is used to initialize when the program begins to start.
     fn write_init(&mut self);
+
+    /// Writes any parsed instruction to the output file.
+    ///
+    /// The `file_name` of the writer is updated from the instruction so static
+    /// variables are named after the file they were declared in.
+    ///
+    /// # Arguments
+    ///
+    /// * `instruction` - The instruction to translate.
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// for instruction in &program.instructions {
+    ///     writer.write_instruction(instruction);
+    /// }
+    /// ```
+    fn write_instruction(&mut self, instruction: &Instruction);
+
+    /// Writes the command held back by `write_instruction`, if any, and the top of the stack
+    /// held in D.
+    ///
+    /// With `options.tail_calls` or `options.specialise` a command may only be written once the
+    /// next command is known, and with `options.cache_top` the top of the stack may not be in
+    /// RAM, so this must be called after the last instruction.
+    fn flush(&mut self);
+}
+
+/// A private interface for translating a single parsed instruction into assembly code.
+trait CodeWriterPrivate {
+    /// Translates an arithmetic command.
+    fn arithmetic(&mut self, other: &Instruction);
+
+    /// Translates a push or pop command.
+    fn push_pop(&mut self, other: &Instruction);
+
+    /// Translates a branch command.
+    fn branch(&mut self, other: &Instruction);
+
+    /// Translates a function command.
+    fn function(&mut self, other: &Instruction);
+
+    /// Translates a `call` immediately followed by `return` by reusing the frame of the caller.
+    fn tail_call(&mut self, call: &Instruction, other: &Instruction);
+
+    /// Translates an instruction with the specialised emitters when enabled, and the templates otherwise.
+    fn dispatch(&mut self, other: &Instruction);
+
+    /// Translates a command held back by `write_instruction` together with the next one.
+    ///
+    /// # Returns
+    ///
+    /// `false` when the pair has no shorter translation, in which case nothing is written.
+    fn combine(&mut self, held: &Instruction, other: &Instruction) -> bool;
+
+    /// Translates `push constant 0/1` and the push and pop of a small index of `local`,
+    /// `argument`, `this` or `that` with shorter code than the templates.
+    ///
+    /// # Returns
+    ///
+    /// `false` for the other commands, in which case nothing is written.
+    fn specialised(&mut self, other: &Instruction) -> bool;
+
+    /// Translates `push constant 1; neg` or `push constant 0; not` as a push of -1.
+    fn push_minus_one(&mut self, push: &Instruction, other: &Instruction);
+
+    /// Translates a push, pop, arithmetic or branch command keeping the top of the stack in D.
+    ///
+    /// # Returns
+    ///
+    /// `false` for the other commands, which are translated by the usual templates once D is
+    /// spilled to the stack.
+    fn cached(&mut self, other: &Instruction) -> bool;
+
+    /// Writes the assembly code of `other` to the output file and records it in the source map.
+    fn emit(&mut self, to_write: &str, other: &Instruction);
+}
+
+/// Represents an optimisation level of the translator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptLevel {
+    /// No optimisation: the program is translated by the templates as written.
+    #[default]
+    O0,
+    /// The cheap local optimisations.
+    O1,
+    /// The optimisations making the program faster, even when it grows.
+    O2,
+    /// The optimisations making the program smaller.
+    Os,
+}
+
+impl OptLevel {
+    /// Parses a `-O0`, `-O1`, `-O2` or `-Os` command-line option.
+    pub fn parse(option: &str) -> Option<OptLevel> {
+        match option {
+            "-O0" => Some(OptLevel::O0),
+            "-O1" => Some(OptLevel::O1),
+            "-O2" => Some(OptLevel::O2),
+            "-Os" => Some(OptLevel::Os),
+            _ => None,
+        }
+    }
+}
+
+/// The code generation modes of `CodeWriterClass`, all disabled by default.
+#[derive(Clone, Debug, Default)]
+pub struct CodegenOptions {
+    /// Translate `call f n` followed by `return` as a jump reusing the frame of the caller.
+    pub tail_calls: bool,
+
+    /// Keep the top of the stack in the D register between commands, only writing it to RAM
+    /// before labels, jumps, calls, function declarations and returns.
+    pub cache_top: bool,
+
+    /// Use shorter code for `push constant 0/1/-1`, the small indexes of the `local`,
+    /// `argument`, `this` and `that` segments and a comparison followed by `if-goto`.
+    pub specialise: bool,
+
+    /// The optimisation level, choosing how `function` initialises its local variables:
+    /// one template per variable at `O0`, otherwise unrolled stores or a counted loop.
+    pub level: OptLevel,
 }
 
 /// Represents a code writer responsible for translating VM commands into assembly code and writing them to an output file.
@@ -130,6 +253,33 @@ pub struct CodeWriterClass {
     function_commands: CommandList<String>,
 
     state: State,
+
+    /// The ROM address of every VM command written so far, in writing order.
+    pub source_map: Vec<SourceMapEntry>,
+
+    /// The assembly code written for every source map entry, in the same order.
+    pub code: Vec<String>,
+
+    /// The ROM address of the next Hack instruction to be written.
+    address: usize,
+
+    /// The name of the function being translated.
+    function_name: String,
+
+    /// The ROM words written so far, per VM opcode, function and file.
+    pub rom_usage: RomUsageClass,
+
+    /// The code generation modes.
+    pub options: CodegenOptions,
+
+    /// The command waiting for the next one to know if both have a shorter translation.
+    pending: Option<Instruction>,
+
+    /// `true` when the top of the stack is held in D instead of RAM.
+    top_in_d: bool,
+
+    /// The code writing D to the stack, written before the next command by `emit`.
+    spill: String,
 }
 
 /// CodeWriter is an implementation for the CodeWriterClass, responsible for generating
@@ -193,10 +343,89 @@ impl CodeWriter for CodeWriterClass {
             branch_commands: branch,
             function_commands: function,
             state: State::default(),
+            source_map: Vec::new(),
+            code: Vec::new(),
+            address: 0,
+            function_name: String::new(),
+            rom_usage: RomUsageClass::new(),
+            options: CodegenOptions::default(),
+            pending: None,
+            top_in_d: false,
+            spill: String::new(),
         }
     }
 
     fn write_arithmetic(&mut self, other: &ParserClass) {
+        self.arithmetic(&other.instruction());
+    }
+
+    fn write_push_pop(&mut self, other: &ParserClass) {
+        self.push_pop(&other.instruction());
+    }
+
+    fn write_branch(&mut self, other: &ParserClass) {
+        self.branch(&other.instruction());
+    }
+
+    fn write_function(&mut self, other: &ParserClass) {
+        self.function(&other.instruction());
+    }
+
+    fn write_init(&mut self) {
+        // Write the bootstrap code to the output file.
+        let bootstrap = Instruction {
+            file_name: "bootstrap".to_string(),
+            current_command: "bootstrap".to_string(),
+            ..Default::default()
+        };
+        self.emit("// Bootstrap code\n@256\nD=A\n@SP\nM=D\n@returnAddress.0\nD=A\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\nD=M\n@5\nD=D-A\n@0\nD=D-A\n@ARG\nM=D\n@SP\nD=M\n@LCL\nM=D\n@Sys.init\n0;JMP\n(returnAddress.0)\n(while)\n@while\n0;JMP", &bootstrap);
+    }
+
+    fn write_instruction(&mut self, instruction: &Instruction) {
+        // Statics are named after the file the instruction comes from.
+        if !instruction.file_name.is_empty() {
+            self.file_name = instruction.file_name.clone();
+        }
+        // A command held back is written alone when it has no shorter translation with this one.
+        if let Some(held) = self.pending.take() {
+            if self.combine(&held, instruction) {
+                return;
+            }
+            self.dispatch(&held);
+        }
+        if self.options.cache_top && self.cached(instruction) {
+            return;
+        }
+        let name = instruction.name();
+        let held = match name.as_str() {
+            "call" => self.options.tail_calls,
+            "eq" | "gt" | "lt" => self.options.specialise,
+            "push" => self.options.specialise && matches!(constant_value(instruction), Some(0 | 1)),
+            _ => false,
+        };
+        if held {
+            self.pending = Some(instruction.clone());
+            return;
+        }
+        self.dispatch(instruction);
+    }
+
+    fn flush(&mut self) {
+        if let Some(held) = self.pending.take() {
+            self.dispatch(&held);
+        }
+        if std::mem::take(&mut self.top_in_d) {
+            let end = Instruction {
+                file_name: self.file_name.clone(),
+                ..Default::default()
+            };
+            self.emit(&format!("// spill{SPILL}"), &end);
+        }
+    }
+}
+
+impl CodeWriterPrivate for CodeWriterClass {
+    fn arithmetic(&mut self, other: &Instruction) {
         // List of supported arithmetic commands that require an additional integer argument
         let if_condition: List<String> =
             List::new(vec!["gt".to_string(), "lt".to_string(), "eq".to_string()]);
@@ -213,7 +442,7 @@ impl CodeWriter for CodeWriterClass {
             }
 
             // Write the resulting assembly code to the output file
-            writeln!(self.file, "{to_write}").unwrap();
+            self.emit(&to_write, other);
         } else {
             // Panic if `other` does not contain an arithmetic command
             panic!(
@@ -223,7 +452,7 @@ impl CodeWriter for CodeWriterClass {
         }
     }
 
-    fn write_push_pop(&mut self, other: &ParserClass) {
+    fn push_pop(&mut self, other: &Instruction) {
         // Check if the command is of type PushPop.
         let Some(Command::PushPop(command)) = &other.command_type else {
             // If the command type is not recognized, panic with an error message.
@@ -264,7 +493,7 @@ impl CodeWriter for CodeWriterClass {
                     .replace("{file_name}", &self.file_name[..self.file_name.len() - 3]);
 
                 // Write the translated assembly code to the output file.
-                writeln!(self.file, "{to_write}").unwrap();
+                self.emit(&to_write, other);
             }
 
             // For internal segments (local, argument, this, that), generate the assembly code.
@@ -295,7 +524,7 @@ impl CodeWriter for CodeWriterClass {
                     .replace("{segment}", &segment_to_add);
 
                 // Write the translated assembly code to the output file.
-                writeln!(self.file, "{to_write}").unwrap();
+                self.emit(&to_write, other);
             }
 
             // If the segment type is not recognized, panic with an error message.
@@ -303,7 +532,7 @@ impl CodeWriter for CodeWriterClass {
         }
     }
 
-    fn write_branch(&mut self, other: &ParserClass) {
+    fn branch(&mut self, other: &Instruction) {
         // Check if the command type is a branch command (label, goto, if-goto).
         let Some(Command::Branch(command)) = &other.command_type else {
             // If the command type is not a branch command, panic with an error message.
@@ -319,14 +548,14 @@ impl CodeWriter for CodeWriterClass {
         // Get the corresponding assembly code for the branch command from the map.
         let mut to_write = self.branch_commands.get(command).to_string();
 
-        // Replace the placeholder "{label_name}" in the assembly code with the actual label name.
-        to_write = to_write.replace("{label_name}", label[1]);
+        // Replace the placeholder "{label_name}" in the assembly code with the label of the current function.
+        to_write = to_write.replace("{label_name}", &scoped_label(&self.function_name, label[1]));
 
         // Write the translated assembly code to the output file.
-        writeln!(self.file, "{to_write}").unwrap();
+        self.emit(&to_write, other);
     }
 
-    fn write_function(&mut self, other: &ParserClass) {
+    fn function(&mut self, other: &Instruction) {
         // Check if the command type is a function command.
         let Some(Command::Function(command)) = &other.command_type else {
             // If the command type is not recognized as a function command, panic with an error message.
@@ -356,25 +585,325 @@ impl CodeWriter for CodeWriterClass {
             self.state.inc_function();
         }
 
-        // Write the translated assembly code to the output file.
-        writeln!(self.file, "{to_write}").unwrap();
-
         // If the command is a "function" command, add local variables to the function's stack frame.
         if _command[0] == "function" {
+            self.function_name = _command[1].to_string();
             let vars: usize = _command[2].parse::<usize>().unwrap();
-            // Iterate over the number of local variables and initialize them to 0 on the stack frame.
-            for i in 0..vars {
-                writeln!(
-                    self.file,
-                    "// Add local var(s)\n@{i}\nD=A\n@LCL\nA=M+D\nM=0\n@SP\nM=M+1"
-                )
-                .unwrap();
+            if self.options.level == OptLevel::O0 {
+                // Iterate over the number of local variables and initialize them to 0 on the stack frame.
+                for i in 0..vars {
+                    to_write.push_str(&format!(
+                        "\n// Add local var(s)\n@{i}\nD=A\n@LCL\nA=M+D\nM=0\n@SP\nM=M+1"
+                    ));
+                }
+            } else {
+                to_write.push_str(&locals(_command[1], vars, self.options.level));
             }
         }
+
+        // Write the translated assembly code to the output file.
+        self.emit(&to_write, other);
     }
 
-    fn write_init(&mut self) {
-        // Write the bootstrap code to the output file.
-        writeln!(self.file, "// Bootstrap code\n@256\nD=A\n@SP\nM=D\n@returnAddress.0\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@LCL\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@ARG\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@THIS\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\n@THAT\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1\nD=M\n@5\nD=D-A\n@0\nD=D-A\n@ARG\nM=D\n@SP\nD=M\n@LCL\nM=D\n@Sys.init\n0;JMP\n(returnAddress.0)\n(while)\n@while\n0;JMP").unwrap();
+    fn tail_call(&mut self, call: &Instruction, other: &Instruction) {
+        let function = call.part(1).unwrap_or_default();
+        let n_args: usize = call.part(2).and_then(|n| n.parse().ok()).unwrap_or(0);
+        let block = n_args + 5;
+
+        // Push the frame saved by the caller's caller above the new arguments.
+        let mut to_write = format!("// call {function} {n_args} (tail call)");
+        for offset in (1..=5).rev() {
+            to_write.push_str(&format!(
+                "\n@LCL\nD=M\n@{offset}\nA=D-A\nD=M\n@SP\nA=M\nM=D\n@SP\nM=M+1"
+            ));
+        }
+        // Move the arguments and the frame down to ARG, in increasing addresses as they only move down.
+        to_write.push_str(&format!(
+            "\n@{block}\nD=A\n@SP\nD=M-D\n@13\nM=D\n@ARG\nD=M\n@14\nM=D"
+        ));
+        for _ in 0..block {
+            to_write.push_str("\n@13\nA=M\nD=M\n@14\nA=M\nM=D\n@13\nM=M+1\n@14\nM=M+1");
+        }
+        // The callee starts its frame right after the moved one, with the same ARG.
+        to_write.push_str(&format!(
+            "\n@14\nD=M\n@SP\nM=D\n@LCL\nM=D\n@{function}\n0;JMP"
+        ));
+        self.emit(&to_write, call);
+
+        // The return is never reached: the callee returns to the caller's caller.
+        self.emit("// return (tail call)", other);
+    }
+
+    fn dispatch(&mut self, other: &Instruction) {
+        if self.options.specialise && self.specialised(other) {
+            return;
+        }
+        // Dispatch on the type of the instruction, skipping unrecognised commands.
+        match other.command_type {
+            Some(Command::Arithmetic(_)) => self.arithmetic(other),
+            Some(Command::PushPop(_)) => self.push_pop(other),
+            Some(Command::Branch(_)) => self.branch(other),
+            Some(Command::Function(_)) => self.function(other),
+            None => {}
+        }
+    }
+
+    fn combine(&mut self, held: &Instruction, other: &Instruction) -> bool {
+        let first = held.name();
+        let second = other.name();
+        match (first.as_str(), second.as_str()) {
+            // A call held back is a tail call when the function returns right after it.
+            ("call", "return") => self.tail_call(held, other),
+            // The comparison jumps on its own result instead of pushing it for `if-goto`.
+            ("eq" | "gt" | "lt", "if-goto") => {
+                let label = other.part(1).unwrap_or_default();
+                let target = scoped_label(&self.function_name, label);
+                let jump = first.to_uppercase();
+                self.emit(
+                    &format!("// {first} (fused)\n@SP\nAM=M-1\nD=M\n@SP\nAM=M-1\nD=M-D"),
+                    held,
+                );
+                self.emit(
+                    &format!("// if-goto {label} (fused)\n@{target}\nD;J{jump}"),
+                    other,
+                );
+            }
+            // -1 is written directly instead of being computed from 1 or 0.
+            ("push", "neg") if constant_value(held) == Some(1) => self.push_minus_one(held, other),
+            ("push", "not") if constant_value(held) == Some(0) => self.push_minus_one(held, other),
+            _ => return false,
+        }
+        true
+    }
+
+    fn specialised(&mut self, other: &Instruction) -> bool {
+        let name = other.name();
+        let segment = other.part(1).unwrap_or_default();
+        let index = other.index.unwrap_or(-1);
+        let code = match (name.as_str(), segment) {
+            ("push", "constant") if index == 0 || index == 1 => {
+                format!("\n@SP\nM=M+1\nA=M-1\nM={index}")
+            }
+            ("push", "local" | "argument" | "this" | "that")
+                if (0..=CHAIN_LIMIT).contains(&index) =>
+            {
+                format!("{}\nD=M\n@SP\nM=M+1\nA=M-1\nM=D", chain(segment, index))
+            }
+            ("pop", "local" | "argument" | "this" | "that")
+                if (0..=CHAIN_LIMIT).contains(&index) =>
+            {
+                format!("\n@SP\nAM=M-1\nD=M{}\nM=D", chain(segment, index))
+            }
+            _ => return false,
+        };
+        self.emit(
+            &format!("// {} (specialised){code}", other.current_command),
+            other,
+        );
+        true
+    }
+
+    fn push_minus_one(&mut self, push: &Instruction, other: &Instruction) {
+        self.emit(
+            &format!(
+                "// {} (specialised)\n@SP\nM=M+1\nA=M-1\nM=-1",
+                push.current_command
+            ),
+            push,
+        );
+        self.emit(
+            &format!("// {} (specialised)", other.current_command),
+            other,
+        );
+    }
+
+    fn cached(&mut self, other: &Instruction) -> bool {
+        let name = other.name();
+        let operand = other.part(1).unwrap_or_default();
+        let index = other.index.unwrap_or(0);
+        let module = &self.file_name[..self.file_name.len() - 3];
+        // The code writing D to the stack, and the code reading the top of the stack into D.
+        let spill = if self.top_in_d { SPILL } else { "" };
+        let load = if self.top_in_d {
+            ""
+        } else {
+            "\n@SP\nAM=M-1\nD=M"
+        };
+
+        let label = scoped_label(&self.function_name, operand);
+        let code = match name.as_str() {
+            "push" => format!("{spill}{}", read(operand, index, module)),
+            "pop" => format!("{load}{}", store(operand, index, module)),
+            "neg" => format!("{load}\nD=-D"),
+            "not" => format!("{load}\nD=!D"),
+            "add" => format!("{load}\n@SP\nAM=M-1\nD=D+M"),
+            "sub" => format!("{load}\n@SP\nAM=M-1\nD=M-D"),
+            "and" => format!("{load}\n@SP\nAM=M-1\nD=D&M"),
+            "or" => format!("{load}\n@SP\nAM=M-1\nD=D|M"),
+            "eq" | "gt" | "lt" => {
+                let i = self.state.get_logical();
+                self.state.inc_logical();
+                let jump = name.to_uppercase();
+                format!("{load}\n@SP\nAM=M-1\nD=M-D\n@CON_TRUE_{i}\nD;J{jump}\nD=0\n@CON_FINISH_{i}\n0;JMP\n(CON_TRUE_{i})\nD=-1\n(CON_FINISH_{i})")
+            }
+            "label" => format!("{spill}\n({label})"),
+            "goto" => format!("{spill}\n@{label}\n0;JMP"),
+            "if-goto" => format!("{load}\n@{label}\nD;JNE"),
+            _ => {
+                // Calls, function declarations and returns expect the whole stack in RAM.
+                self.spill = spill.to_string();
+                self.top_in_d = false;
+                return false;
+            }
+        };
+
+        self.top_in_d = !matches!(name.as_str(), "pop" | "label" | "goto" | "if-goto");
+        self.emit(
+            &format!("// {} (top in D){code}", other.current_command),
+            other,
+        );
+        true
+    }
+
+    fn emit(&mut self, to_write: &str, other: &Instruction) {
+        let to_write = match std::mem::take(&mut self.spill) {
+            spill if spill.is_empty() => to_write.to_string(),
+            spill => format!("// spill{spill}\n{to_write}"),
+        };
+        let to_write = to_write.as_str();
+        writeln!(self.file, "{to_write}").unwrap();
+
+        // Labels and comments do not take any ROM space.
+        let length = to_write.lines().filter(|line| is_instruction(line)).count();
+        let file_name = if other.file_name.is_empty() {
+            self.file_name.clone()
+        } else {
+            other.file_name.clone()
+        };
+        let entry = SourceMapEntry {
+            address: self.address,
+            length,
+            file_name,
+            line: other.line,
+            command: other.current_command.clone(),
+            function: self.function_name.clone(),
+        };
+        self.rom_usage.add(&entry);
+        self.source_map.push(entry);
+        self.code.push(to_write.to_string());
+        self.address += length;
+    }
+}
+
+/// The number of local variables from which `locals` writes a loop rather than one store per
+/// variable, per optimisation level. The loop takes 12 words and 6n + 6 cycles, the stores
+/// 2n + 5 words and cycles, so the loop is smaller from 4 variables but slower for any number.
+///
+/// `Os` loops as soon as it is smaller, `O1` once it is at most half the size of the stores
+/// (2 * 12 <= 2n + 5), and `O2` never loops.
+fn loop_threshold(level: OptLevel) -> usize {
+    match level {
+        OptLevel::O0 | OptLevel::O2 => usize::MAX,
+        OptLevel::O1 => 10,
+        OptLevel::Os => 4,
+    }
+}
+
+/// Returns the code setting the `vars` local variables of `function` to 0 and moving SP past them once.
+fn locals(function: &str, vars: usize, level: OptLevel) -> String {
+    let mut code = String::new();
+    if vars == 0 {
+        return code;
+    }
+    if vars < loop_threshold(level) {
+        code.push_str("\n// Add local var(s) unrolled\n@LCL\nA=M\nM=0");
+        code.push_str(&"\nA=A+1\nM=0".repeat(vars - 1));
+    } else {
+        // D counts the variables down, storing LCL[D] until D reaches 0.
+        code.push_str(&format!(
+            "\n// Add local var(s) loop\n@{vars}\nD=A\n({function}.locals)\nD=D-1\n@LCL\nA=D+M\nM=0\n@{function}.locals\nD;JGT"
+        ));
+    }
+    code.push_str(&format!("\n@{vars}\nD=A\n@SP\nM=D+M"));
+    code
+}
+
+/// The largest segment index addressed by an `A=M+1` chain rather than by adding it in D.
+const CHAIN_LIMIT: i32 = 3;
+
+/// Returns the code pointing A to `segment[index]` without using D, for an index up to `CHAIN_LIMIT`.
+fn chain(segment: &str, index: i32) -> String {
+    let mut code = format!("\n@{}", base(segment));
+    match index {
+        0 => code.push_str("\nA=M"),
+        _ => {
+            code.push_str("\nA=M+1");
+            code.push_str(&"\nA=A+1".repeat(index as usize - 1));
+        }
+    }
+    code
+}
+
+/// Returns the value pushed by a `push constant` command.
+fn constant_value(instruction: &Instruction) -> Option<i32> {
+    match (instruction.name().as_str(), instruction.part(1)) {
+        ("push", Some("constant")) => instruction.index,
+        _ => None,
     }
 }
+
+/// Writes D to the top of the stack.
+const SPILL: &str = "\n@SP\nA=M\nM=D\n@SP\nM=M+1";
+
+/// Returns the name of the register holding the base address of a segment.
+fn base(segment: &str) -> &'static str {
+    match segment {
+        "local" => "LCL",
+        "argument" => "ARG",
+        "this" => "THIS",
+        "that" => "THAT",
+        _ => panic!("Segment {segment} has no base address"),
+    }
+}
+
+/// Returns the code loading `segment[index]` into D.
+fn read(segment: &str, index: i32, module: &str) -> String {
+    match segment {
+        "constant" => format!("\n@{index}\nD=A"),
+        "static" => format!("\n@{module}.{index}\nD=M"),
+        "temp" => format!("\n@{}\nD=M", 5 + index),
+        "pointer" => format!("\n@{}\nD=M", ["THIS", "THAT"][usize::from(index == 1)]),
+        _ if index == 0 => format!("\n@{}\nA=M\nD=M", base(segment)),
+        _ => format!("\n@{index}\nD=A\n@{}\nA=M+D\nD=M", base(segment)),
+    }
+}
+
+/// Returns the code storing D into `segment[index]`, using R13 and R14 for computed addresses.
+fn store(segment: &str, index: i32, module: &str) -> String {
+    match segment {
+        "static" => format!("\n@{module}.{index}\nM=D"),
+        "temp" => format!("\n@{}\nM=D", 5 + index),
+        "pointer" => format!("\n@{}\nM=D", ["THIS", "THAT"][usize::from(index == 1)]),
+        "constant" => panic!("Cannot pop into the constant segment"),
+        _ if index == 0 => format!("\n@{}\nA=M\nM=D", base(segment)),
+        _ => format!(
+            "\n@13\nM=D\n@{index}\nD=A\n@{}\nD=M+D\n@14\nM=D\n@13\nD=M\n@14\nA=M\nM=D",
+            base(segment)
+        ),
+    }
+}
+
+/// Returns the assembly symbol of a VM label, `<function>$<label>` inside a function so that
+/// functions may declare the same labels, the label itself for the code before the first function.
+pub fn scoped_label(function: &str, label: &str) -> String {
+    match function.is_empty() {
+        true => label.to_string(),
+        false => format!("{function}${label}"),
+    }
+}
+
+/// Checks if a line of assembly code is a Hack instruction, as opposed to a label, a comment or a blank line.
+pub fn is_instruction(line: &str) -> bool {
+    let line = line.trim();
+    !(line.is_empty() || line.starts_with("//") || line.starts_with('('))
+}
@@ -0,0 +1,142 @@
+use super::program::*;
+use super::vm_interpreter::arithmetic;
+use crate::prelude::*;
+
+/// A public interface for evaluating constant expressions before code generation.
+pub trait ConstantFoldingPublic {
+    /// Creates a new instance of the pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to optimise.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Replaces the arithmetic, comparison and logical commands whose operands are all pushed
+    /// by constants just before them with the constant they compute.
+    ///
+    /// The values follow the VM semantics of `InterpreterClass`: 16-bit two's-complement
+    /// wrapping for `add`, `sub` and `neg`, and `-1` (true) or `0` (false) for `eq`, `gt`, `lt`.
+    /// A negative result `v` is pushed as `push constant !v; not`, as `push constant` only takes
+    /// values from 0 to 32767. Folding stops at labels, so values reaching a label from a jump
+    /// are never assumed.
+    ///
+    /// # Returns
+    ///
+    /// The optimised program.
+    fn fold(&mut self) -> ProgramClass;
+}
+
+/// Represents the constant folding pass over a VM instruction stream.
+#[derive(Clone, Debug, Default)]
+pub struct ConstantFoldingClass {
+    /// The program to optimise.
+    pub program: ProgramClass,
+
+    /// The number of arithmetic commands evaluated by the last `fold`.
+    pub folded: usize,
+
+    /// The number of VM commands removed by the last `fold`.
+    pub removed: usize,
+}
+
+impl ConstantFoldingPublic for ConstantFoldingClass {
+    fn new(program: &ProgramClass) -> Self {
+        ConstantFoldingClass {
+            program: program.clone(),
+            folded: 0,
+            removed: 0,
+        }
+    }
+
+    fn fold(&mut self) -> ProgramClass {
+        let mut output: Vec<Instruction> = Vec::new();
+        // The values pushed by the constants at the end of `output`, with the position of their first command.
+        let mut constants: Vec<(i16, usize)> = Vec::new();
+        self.folded = 0;
+
+        for instruction in &self.program.instructions {
+            let name = instruction.name();
+            let operands = match name.as_str() {
+                "neg" | "not" => 1,
+                "add" | "sub" | "and" | "or" | "eq" | "gt" | "lt" => 2,
+                _ => 0,
+            };
+
+            if operands > 0 && constants.len() >= operands {
+                let y = constants.pop().unwrap();
+                let (value, start) = match operands {
+                    1 if name == "neg" => (y.0.wrapping_neg(), y.1),
+                    1 => (!y.0, y.1),
+                    _ => {
+                        let x = constants.pop().unwrap();
+                        (arithmetic(&name, x.0, y.0), x.1)
+                    }
+                };
+                let replacement = push_constant(value, instruction);
+                let unchanged = output[start..]
+                    .iter()
+                    .chain([instruction])
+                    .map(|instruction| instruction.current_command.as_str())
+                    .eq(replacement
+                        .iter()
+                        .map(|instruction| instruction.current_command.as_str()));
+                if unchanged {
+                    output.push(instruction.clone());
+                } else {
+                    output.truncate(start);
+                    output.extend(replacement);
+                    self.folded += 1;
+                }
+                constants.push((value, start));
+                continue;
+            }
+
+            // Any other command leaves a value the pass does not know on the stack, or jumps.
+            match constant_value(instruction) {
+                Some(value) => constants.push((value, output.len())),
+                None => constants.clear(),
+            }
+            output.push(instruction.clone());
+        }
+
+        self.removed = self.program.instructions.len() - output.len();
+        ProgramClass {
+            instructions: output,
+        }
+    }
+}
+
+/// Returns the value pushed by a `push constant` command.
+fn constant_value(instruction: &Instruction) -> Option<i16> {
+    if instruction.name() != "push" || instruction.part(1) != Some("constant") {
+        return None;
+    }
+    instruction
+        .index
+        .filter(|index| (0..=i32::from(i16::MAX)).contains(index))
+        .map(|index| index as i16)
+}
+
+/// Returns the commands pushing `value`, located at `origin`.
+fn push_constant(value: i16, origin: &Instruction) -> Vec<Instruction> {
+    let push = |index: i16| Instruction {
+        current_command: format!("push constant {index}"),
+        command_type: Some(Command::PushPop("push".to_string())),
+        segment_type: Some(Segment::External("constant".to_string())),
+        index: Some(i32::from(index)),
+        ..origin.clone()
+    };
+    match value {
+        0.. => vec![push(value)],
+        _ => vec![
+            push(!value),
+            Instruction {
+                current_command: "not".to_string(),
+                command_type: Some(Command::Arithmetic("not".to_string())),
+                segment_type: None,
+                index: None,
+                ..origin.clone()
+            },
+        ],
+    }
+}
@@ -0,0 +1,145 @@
+use super::cfg::*;
+use super::program::*;
+use std::collections::{HashSet, VecDeque};
+
+/// A public interface for removing the code of a function that can never run.
+pub trait DeadCodePublic {
+    /// Creates a new instance of the pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to optimise.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Removes the commands of the basic blocks no path from the start of their function
+    /// reaches, then the labels of the functions no remaining `goto` or `if-goto` jumps to,
+    /// unreachable or not.
+    ///
+    /// Reachability follows the edges of `CfgClass`: a call continues with the next block.
+    /// A function jumping to a label it does not declare is left untouched, as its control
+    /// flow is unknown. The commands kept are not changed, and code outside of any function
+    /// is kept as it is.
+    ///
+    /// # Returns
+    ///
+    /// The optimised program.
+    fn eliminate(&mut self) -> ProgramClass;
+}
+
+/// Represents the dead code elimination pass over a VM instruction stream.
+#[derive(Clone, Debug, Default)]
+pub struct DeadCodeClass {
+    /// The program to optimise.
+    pub program: ProgramClass,
+
+    /// The number of unreachable commands removed by the last `eliminate`, labels excluded.
+    pub removed: usize,
+
+    /// The number of labels removed by the last `eliminate`, unreachable or unreferenced.
+    pub labels: usize,
+}
+
+impl DeadCodePublic for DeadCodeClass {
+    fn new(program: &ProgramClass) -> Self {
+        DeadCodeClass {
+            program: program.clone(),
+            removed: 0,
+            labels: 0,
+        }
+    }
+
+    fn eliminate(&mut self) -> ProgramClass {
+        let instructions = &self.program.instructions;
+        let mut keep = vec![true; instructions.len()];
+        // The position of the labels that may be removed when nothing jumps to them.
+        let mut labels: Vec<usize> = Vec::new();
+        self.removed = 0;
+        self.labels = 0;
+
+        for function in self.program.functions() {
+            let cfg = CfgClass::new(&self.program, &function);
+            if !resolved(&cfg) {
+                continue;
+            }
+            let reachable = reachable(&cfg);
+            for (number, block) in cfg.blocks.iter().enumerate() {
+                for position in block.start..block.end {
+                    // Labels, even unreachable ones, are only removed when nothing jumps to them.
+                    if cfg.instructions[position].name() == "label" {
+                        labels.push(function.start + position);
+                    } else if !reachable[number] {
+                        keep[function.start + position] = false;
+                        self.removed += 1;
+                    }
+                }
+            }
+        }
+
+        // Jumps are matched to labels by name only, so the jumps of every kept command count.
+        let targets: HashSet<&str> = instructions
+            .iter()
+            .zip(&keep)
+            .filter(|(instruction, kept)| {
+                **kept && matches!(instruction.name().as_str(), "goto" | "if-goto")
+            })
+            .filter_map(|(instruction, _)| instruction.part(1))
+            .collect();
+        for position in labels {
+            let label = instructions[position].part(1).unwrap_or_default();
+            if !targets.contains(label) {
+                keep[position] = false;
+                self.labels += 1;
+            }
+        }
+
+        ProgramClass {
+            instructions: instructions
+                .iter()
+                .zip(keep)
+                .filter(|(_, kept)| *kept)
+                .map(|(instruction, _)| instruction.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Returns `true` when every `goto` and `if-goto` of the function jumps to a label it declares once.
+fn resolved(cfg: &CfgClass) -> bool {
+    let mut declared: HashSet<&str> = HashSet::new();
+    let unique = cfg
+        .instructions
+        .iter()
+        .filter(|instruction| instruction.name() == "label")
+        .all(|instruction| declared.insert(instruction.part(1).unwrap_or_default()));
+    unique
+        && cfg.blocks.iter().all(|block| {
+            let last = &cfg.instructions[block.end - 1];
+            match last.name().as_str() {
+                "goto" => matches!(block.edges.first(), Some(Edge::Goto(_))),
+                "if-goto" => matches!(block.edges.first(), Some(Edge::Branch(_))),
+                _ => true,
+            }
+        })
+}
+
+/// Returns which blocks of the function can run, starting from its first block.
+fn reachable(cfg: &CfgClass) -> Vec<bool> {
+    let mut reachable = vec![false; cfg.blocks.len()];
+    let mut queue: VecDeque<usize> = VecDeque::from([0]);
+    while let Some(block) = queue.pop_front() {
+        if block >= reachable.len() || reachable[block] {
+            continue;
+        }
+        reachable[block] = true;
+        for edge in &cfg.blocks[block].edges {
+            match edge {
+                Edge::FallThrough(next) | Edge::Goto(next) | Edge::Branch(next) => {
+                    queue.push_back(*next);
+                }
+                Edge::Call(_, next) => queue.push_back(*next),
+                Edge::Return => {}
+            }
+        }
+    }
+    reachable
+}
@@ -0,0 +1,347 @@
+use super::program::*;
+use super::vm_interpreter::*;
+use crate::prelude::*;
+use std::io::{BufRead, Write};
+
+const HELP: &str = "Commands:
+  break <file>:<line> | break <function>   Stop before a line or when entering a function
+  delete [n]                                Delete breakpoint n, or every breakpoint
+  info breakpoints                          List the breakpoints
+  continue | c                              Run until a breakpoint or the end of the program
+  step | s                                  Execute one VM command
+  next | n                                  Execute one VM command, running calls to completion
+  finish                                    Run until the current function returns
+  print stack|local|argument|pointer|temp   Print a segment of the current function
+  print this|that [count]                   Print the first count (default 8) values of this/that
+  print static                              Print the static segment of the current file
+  backtrace | bt                            Print the call stack
+  where                                     Print the next VM command
+  quit | q                                  Stop debugging";
+
+/// A public interface for debugging VM programs on the reference interpreter.
+pub trait DebuggerPublic {
+    /// Creates a new instance of the debugger, stopped before the first VM command.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to debug.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Executes one debugger command.
+    ///
+    /// # Arguments
+    ///
+    /// * `command` - The command line, e.g. `break Main.vm:12`, `step` or `print local`.
+    ///
+    /// # Returns
+    ///
+    /// The text to show for the command.
+    fn execute(&mut self, command: &str) -> String;
+
+    /// Executes debugger commands read from `input` until `quit` or the end of the input.
+    ///
+    /// Every command is echoed after a `(vmdb)` prompt, so a scripted session reads like
+    /// an interactive one in CI logs.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The commands, one per line. Blank lines and lines starting with `#` are ignored.
+    /// * `output` - Where the commands and their results are written.
+    fn run_script(&mut self, input: &mut dyn BufRead, output: &mut dyn Write);
+}
+
+/// Represents a place to stop the execution at.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Breakpoint {
+    /// Stops before the VM command at the given file and line.
+    Line(String, usize),
+    /// Stops when entering the named function.
+    Function(String),
+}
+
+/// Represents a line-oriented debugger over `InterpreterClass`.
+pub struct DebuggerClass {
+    /// The interpreter executing the program.
+    pub interpreter: InterpreterClass,
+
+    /// The breakpoints, numbered from 1 in the order they were set.
+    pub breakpoints: Vec<Option<Breakpoint>>,
+
+    /// The maximum number of VM commands a single command may execute.
+    pub max_steps: usize,
+
+    /// `true` once `quit` has been executed.
+    pub quit: bool,
+
+    /// `true` once a command has executed the program, which then stopped before `pc`.
+    pub stopped: bool,
+}
+
+impl DebuggerPublic for DebuggerClass {
+    fn new(program: &ProgramClass) -> Self {
+        DebuggerClass {
+            interpreter: InterpreterClass::new(program),
+            breakpoints: Vec::new(),
+            max_steps: 10_000_000,
+            quit: false,
+            stopped: false,
+        }
+    }
+
+    fn execute(&mut self, command: &str) -> String {
+        let parts: Vec<&str> = command.split_whitespace().collect();
+        match parts.as_slice() {
+            [] => String::new(),
+            ["break" | "b", location] => self.add_breakpoint(location),
+            ["delete"] => {
+                self.breakpoints.clear();
+                "Deleted every breakpoint".to_string()
+            }
+            ["delete", number] => match number.parse::<usize>() {
+                Ok(n)
+                    if self
+                        .breakpoints
+                        .get(n.wrapping_sub(1))
+                        .is_some_and(Option::is_some) =>
+                {
+                    self.breakpoints[n - 1] = None;
+                    format!("Deleted breakpoint {n}")
+                }
+                _ => format!("No breakpoint {number}"),
+            },
+            ["info", "breakpoints"] => self.list_breakpoints(),
+            ["continue" | "c"] => self.resume(|_, _| false),
+            ["step" | "s"] => {
+                self.stopped = true;
+                self.interpreter.step();
+                self.stop_reason(None)
+            }
+            ["next" | "n"] => {
+                let depth = self.interpreter.frames.len();
+                self.resume(move |interpreter, _| interpreter.frames.len() <= depth)
+            }
+            ["finish"] => {
+                let depth = self.interpreter.frames.len();
+                self.resume(move |interpreter, _| interpreter.frames.len() < depth)
+            }
+            ["print" | "p", segment] => self.print(segment, 8),
+            ["print" | "p", segment, count] => match count.parse() {
+                Ok(count) => self.print(segment, count),
+                Err(_) => format!("Invalid count {count}"),
+            },
+            ["backtrace" | "bt"] => self.backtrace(),
+            ["where"] => self.location(),
+            ["help" | "h"] => HELP.to_string(),
+            ["quit" | "q"] => {
+                self.quit = true;
+                String::new()
+            }
+            _ => format!("Unknown command {command:?}, type help for the list of commands"),
+        }
+    }
+
+    fn run_script(&mut self, input: &mut dyn BufRead, output: &mut dyn Write) {
+        let mut line = String::new();
+        while !self.quit {
+            line.clear();
+            if input.read_line(&mut line).unwrap_or(0) == 0 {
+                break;
+            }
+            let command = line.trim();
+            if command.is_empty() || command.starts_with('#') {
+                continue;
+            }
+            writeln!(output, "(vmdb) {command}").unwrap();
+            let result = self.execute(command);
+            if !result.is_empty() {
+                writeln!(output, "{result}").unwrap();
+            }
+        }
+    }
+}
+
+impl DebuggerClass {
+    /// Parses `location` (`<file>:<line>` or a function name) and adds the breakpoint.
+    fn add_breakpoint(&mut self, location: &str) -> String {
+        let breakpoint = match location.rsplit_once(':') {
+            Some((file, line)) => match line.parse() {
+                Ok(line) => Breakpoint::Line(file.to_string(), line),
+                Err(_) => return format!("Invalid line {line}"),
+            },
+            None => Breakpoint::Function(location.to_string()),
+        };
+        self.breakpoints.push(Some(breakpoint));
+        format!("Breakpoint {} at {location}", self.breakpoints.len())
+    }
+
+    fn list_breakpoints(&self) -> String {
+        let lines: Vec<String> = self
+            .breakpoints
+            .iter()
+            .enumerate()
+            .filter_map(|(n, breakpoint)| {
+                breakpoint.as_ref().map(|breakpoint| match breakpoint {
+                    Breakpoint::Line(file, line) => format!("{}: {file}:{line}", n + 1),
+                    Breakpoint::Function(function) => format!("{}: {function}", n + 1),
+                })
+            })
+            .collect();
+        if lines.is_empty() {
+            return "No breakpoints".to_string();
+        }
+        lines.join("\n")
+    }
+
+    /// Returns the number of the breakpoint set on the next VM command, if any.
+    fn breakpoint_hit(&self) -> Option<usize> {
+        let instruction = self.next_instruction()?;
+        self.breakpoints
+            .iter()
+            .position(|breakpoint| match breakpoint {
+                Some(Breakpoint::Line(file, line)) => {
+                    *line == instruction.line
+                        && (*file == instruction.file_name
+                            || *file == file_stem(&instruction.file_name))
+                }
+                Some(Breakpoint::Function(function)) => {
+                    instruction.name() == "function" && instruction.part(1) == Some(function)
+                }
+                None => false,
+            })
+    }
+
+    /// Executes commands until `done` returns true after a command, a breakpoint is reached
+    /// or the program halts.
+    fn resume<F>(&mut self, done: F) -> String
+    where
+        F: Fn(&InterpreterClass, &Step) -> bool,
+    {
+        // A breakpoint on the first command stops before it runs. Once stopped, the breakpoint
+        // of the next command has already been reported.
+        if !std::mem::replace(&mut self.stopped, true) {
+            if let Some(number) = self.breakpoint_hit() {
+                return self.stop_reason(Some(number));
+            }
+        }
+        for _ in 0..self.max_steps {
+            let Some(step) = self.interpreter.step() else {
+                return self.stop_reason(None);
+            };
+            if let Some(number) = self.breakpoint_hit() {
+                return self.stop_reason(Some(number));
+            }
+            if done(&self.interpreter, &step) {
+                return self.stop_reason(None);
+            }
+        }
+        format!(
+            "Stopped after {} commands\n{}",
+            self.max_steps,
+            self.location()
+        )
+    }
+
+    /// Describes why the execution stopped and where.
+    fn stop_reason(&self, breakpoint: Option<usize>) -> String {
+        if let Some(error) = &self.interpreter.error {
+            return format!("Program failed: {error}");
+        }
+        if self.interpreter.halted {
+            return format!("Program halted after {} commands", self.interpreter.steps);
+        }
+        match breakpoint {
+            Some(number) => format!("Breakpoint {}, {}", number + 1, self.location()),
+            None => self.location(),
+        }
+    }
+
+    fn next_instruction(&self) -> Option<&Instruction> {
+        self.interpreter.instructions.get(self.interpreter.pc)
+    }
+
+    /// Describes the next VM command.
+    fn location(&self) -> String {
+        match self.next_instruction() {
+            Some(instruction) if !self.interpreter.halted => format!(
+                "{}:{}: {}",
+                instruction.file_name, instruction.line, instruction.current_command
+            ),
+            _ => "The program is not running".to_string(),
+        }
+    }
+
+    fn print(&self, segment: &str, count: usize) -> String {
+        let interpreter = &self.interpreter;
+        let frame = interpreter.frame();
+        let values: Vec<String> = match segment {
+            "stack" => numbered(&frame.stack),
+            "local" => numbered(&frame.local),
+            "argument" => numbered(&frame.argument),
+            "pointer" => numbered(&interpreter.pointer),
+            "temp" => numbered(&interpreter.temp),
+            "this" | "that" => {
+                let base = interpreter.pointer[usize::from(segment == "that")] as u16 as usize;
+                (0..count)
+                    .map(|i| {
+                        let address = (base + i) & 0x7fff;
+                        let value = interpreter.heap.get(&address).copied().unwrap_or(0);
+                        format!("[{i}] {value}   (RAM[{address}])")
+                    })
+                    .collect()
+            }
+            "static" => {
+                let Some(instruction) = self.next_instruction() else {
+                    return "The program is not running".to_string();
+                };
+                let prefix = format!("{}.", file_stem(&instruction.file_name));
+                interpreter
+                    .statics
+                    .iter()
+                    .filter_map(|(name, value)| {
+                        let index = name.strip_prefix(&prefix)?;
+                        Some((index.parse::<usize>().ok()?, *value))
+                    })
+                    .map(|(index, value)| format!("[{index}] {value}"))
+                    .collect()
+            }
+            _ => return format!("Unknown segment {segment}"),
+        };
+        if values.is_empty() {
+            return format!("{segment} is empty");
+        }
+        values.join("\n")
+    }
+
+    /// Lists the frames from the current function to the outermost one.
+    fn backtrace(&self) -> String {
+        let instructions = &self.interpreter.instructions;
+        let mut lines: Vec<String> = Vec::new();
+        let mut position = self.interpreter.pc;
+
+        for (depth, frame) in self.interpreter.frames.iter().rev().enumerate() {
+            let name = match frame.function.is_empty() {
+                true => TOP_LEVEL,
+                false => &frame.function,
+            };
+            match instructions.get(position) {
+                Some(instruction) => lines.push(format!(
+                    "#{depth} {name} at {}:{}",
+                    instruction.file_name, instruction.line
+                )),
+                None => lines.push(format!("#{depth} {name}")),
+            }
+            // The caller resumes after its call command.
+            position = frame.return_address.wrapping_sub(1);
+        }
+        lines.join("\n")
+    }
+}
+
+/// Formats the values of a segment as `[index] value` lines.
+fn numbered(values: &[i16]) -> Vec<String> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| format!("[{index}] {value}"))
+        .collect()
+}
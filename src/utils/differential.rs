@@ -0,0 +1,295 @@
+use super::assembler::*;
+use super::code_writer::*;
+use super::emulator::*;
+use super::program::*;
+use super::vm_interpreter::*;
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::ops::Range;
+
+/// The number of Hack instructions the emulator may execute for each VM instruction.
+const CYCLES_PER_STEP: usize = 200;
+
+/// The RAM addresses of the heap, compared in full so stray writes of the translated code show up.
+const HEAP: Range<usize> = 2048..16384;
+
+/// A public interface for checking the translation of a VM program against the reference interpreter.
+pub trait DifferentialPublic {
+    /// Creates a new instance of the harness, translating the program with `CodeWriterClass`.
+    ///
+    /// The program must declare `Sys.init`, which the bootstrap code calls.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Paths of the `.vm` files or directories of the program.
+    /// * `output_file` - The path of the assembly file to write, kept for inspection.
+    fn new(paths: Vec<String>, output_file: String) -> Self;
+
+    /// Creates a new instance of the harness comparing a transformed program with the original one.
+    ///
+    /// `reference` runs on the interpreter and `translated`, e.g. the output of an optimisation
+    /// pass, is translated with the code generation `options` and runs on the emulator. Both
+    /// must return from the same functions in the same order, except that with tail calls the
+    /// returns to a `return` are not compared, as the translation skips them.
+    ///
+    /// # Arguments
+    ///
+    /// * `reference` - The program giving the expected results.
+    /// * `translated` - The program to translate.
+    /// * `options` - The code generation modes of the writer.
+    /// * `output_file` - The path of the assembly file to write, kept for inspection.
+    fn from_programs(
+        reference: &ProgramClass,
+        translated: &ProgramClass,
+        options: &CodegenOptions,
+        output_file: String,
+    ) -> Self;
+
+    /// Runs the program on the interpreter and on the emulator, comparing both after every function return.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_steps` - The maximum number of VM instructions to execute.
+    ///
+    /// # Returns
+    ///
+    /// The number of compared returns, or a description of the first difference found.
+    fn run(&mut self, max_steps: usize) -> Result<usize, String>;
+}
+
+/// Represents the state visible to the caller right after a function returned.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Snapshot {
+    /// The name of the function that returned.
+    pub function: String,
+    /// The working stack of the caller.
+    pub stack: Vec<i16>,
+    /// The `local` segment of the caller.
+    pub local: Vec<i16>,
+    /// The `argument` segment of the caller.
+    pub argument: Vec<i16>,
+    /// The `pointer` segment.
+    pub pointer: Vec<i16>,
+    /// The `temp` segment.
+    pub temp: Vec<i16>,
+    /// Every static variable, keyed by its assembly symbol.
+    pub statics: BTreeMap<String, i16>,
+    /// The non-zero words of the heap and the other memory written through `this` and `that`,
+    /// keyed by address.
+    pub heap: BTreeMap<usize, i16>,
+}
+
+/// Represents a differential test of `CodeWriterClass` against `InterpreterClass`.
+pub struct DifferentialClass {
+    /// The reference interpreter.
+    pub interpreter: InterpreterClass,
+
+    /// The emulator running the translated program.
+    pub emulator: EmulatorClass,
+
+    /// The assembled translation, used to locate symbols.
+    pub assembler: AssemblerClass,
+
+    /// The ROM addresses the `return` code jumps back to.
+    return_addresses: HashSet<usize>,
+
+    /// `true` when the translation uses tail calls.
+    tail_calls: bool,
+}
+
+impl DifferentialPublic for DifferentialClass {
+    fn new(paths: Vec<String>, output_file: String) -> Self {
+        let program = ProgramClass::new(paths);
+        DifferentialClass::from_programs(
+            &program,
+            &program,
+            &CodegenOptions::default(),
+            output_file,
+        )
+    }
+
+    fn from_programs(
+        reference: &ProgramClass,
+        translated: &ProgramClass,
+        options: &CodegenOptions,
+        output_file: String,
+    ) -> Self {
+        // Translate the program the same way the translator does.
+        {
+            let mut writer = CodeWriterClass::new(output_file.clone());
+            writer.options = options.clone();
+            writer.write_init();
+            for instruction in &translated.instructions {
+                writer.write_instruction(instruction);
+            }
+            writer.flush();
+        }
+
+        let source = fs::read_to_string(&output_file).expect("Cannot read translated file");
+        let assembler = AssemblerClass::new(&source);
+        let return_addresses = assembler
+            .labels
+            .iter()
+            .filter(|(label, _)| label.contains(".ret."))
+            .map(|(_, address)| *address as usize)
+            .collect();
+
+        DifferentialClass {
+            interpreter: InterpreterClass::new(reference),
+            emulator: EmulatorClass::new(assembler.rom.clone()),
+            assembler,
+            return_addresses,
+            tail_calls: options.tail_calls,
+        }
+    }
+
+    fn run(&mut self, max_steps: usize) -> Result<usize, String> {
+        let mut compared = 0;
+
+        while self.interpreter.steps < max_steps {
+            let Some(step) = self.interpreter.step() else {
+                break;
+            };
+            let Step::Return(function) = step else {
+                continue;
+            };
+            if self.interpreter.halted {
+                break;
+            }
+            // A tail call returns straight to the caller of the function making it.
+            let pc = self.interpreter.pc;
+            if self.tail_calls
+                && self
+                    .interpreter
+                    .instructions
+                    .get(pc)
+                    .is_some_and(|instruction| instruction.name() == "return")
+            {
+                continue;
+            }
+            let expected = self.interpreter_snapshot(function);
+
+            // Run the translation up to the matching return.
+            self.run_to_return(max_steps * CYCLES_PER_STEP)?;
+            let actual = self.emulator_snapshot(&expected);
+            if actual != expected {
+                return Err(format!(
+                    "After return #{} from {}:\nexpected {expected:?}\nfound    {actual:?}",
+                    compared + 1,
+                    expected.function
+                ));
+            }
+            compared += 1;
+        }
+
+        match &self.interpreter.error {
+            Some(error) => Err(format!("Interpreter failed: {error}")),
+            None => Ok(compared),
+        }
+    }
+}
+
+impl DifferentialClass {
+    /// Executes the emulator until `pc` reaches a return address.
+    fn run_to_return(&mut self, max_cycles: usize) -> Result<(), String> {
+        loop {
+            if self.emulator.is_halted() || self.emulator.cycles >= max_cycles {
+                return Err(format!(
+                    "Emulator stopped at PC {} after {} cycles without returning",
+                    self.emulator.pc, self.emulator.cycles
+                ));
+            }
+            self.emulator.step();
+            if self.return_addresses.contains(&self.emulator.pc) {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Names every static variable of the translation.
+    fn static_symbols(&self) -> Vec<&String> {
+        self.assembler
+            .variables
+            .keys()
+            .filter(|name| {
+                name.rsplit_once('.')
+                    .is_some_and(|(_, index)| index.parse::<u16>().is_ok())
+            })
+            .collect()
+    }
+
+    fn interpreter_snapshot(&self, function: String) -> Snapshot {
+        let frame = self.interpreter.frame();
+        let statics = self
+            .static_symbols()
+            .into_iter()
+            .map(|name| {
+                let value = self.interpreter.statics.get(name).copied().unwrap_or(0);
+                (name.clone(), value)
+            })
+            .collect();
+        Snapshot {
+            function,
+            stack: frame.stack.clone(),
+            local: frame.local.clone(),
+            argument: frame.argument.clone(),
+            pointer: self.interpreter.pointer.to_vec(),
+            temp: self.interpreter.temp.to_vec(),
+            statics,
+            heap: self
+                .interpreter
+                .heap
+                .iter()
+                .filter(|(address, value)| **value != 0 || !HEAP.contains(address))
+                .map(|(address, value)| (*address, *value))
+                .collect(),
+        }
+    }
+
+    /// Reads the caller state from RAM, using `expected` for the size of the segments.
+    fn emulator_snapshot(&self, expected: &Snapshot) -> Snapshot {
+        let ram = &self.emulator.ram;
+        let (sp, lcl, arg) = (ram[0] as usize, ram[1] as usize, ram[2] as usize);
+        let locals_end = lcl + expected.local.len();
+
+        // The return address label is named `<function>.ret.<n>`.
+        let function = self
+            .assembler
+            .labels
+            .iter()
+            .filter(|(label, _)| label.contains(".ret."))
+            .find(|(_, address)| **address as usize == self.emulator.pc)
+            .and_then(|(label, _)| label.split(".ret.").next())
+            .unwrap_or_default()
+            .to_string();
+        let statics = self
+            .static_symbols()
+            .into_iter()
+            .map(|name| (name.clone(), ram[self.assembler.variables[name] as usize]))
+            .collect();
+
+        Snapshot {
+            function,
+            stack: ram.get(locals_end..sp).unwrap_or_default().to_vec(),
+            local: ram.get(lcl..locals_end).unwrap_or_default().to_vec(),
+            argument: ram
+                .get(arg..arg + expected.argument.len())
+                .unwrap_or_default()
+                .to_vec(),
+            pointer: ram[3..5].to_vec(),
+            temp: ram[5..13].to_vec(),
+            statics,
+            heap: HEAP
+                .filter(|address| ram[*address] != 0)
+                .chain(
+                    expected
+                        .heap
+                        .keys()
+                        .copied()
+                        .filter(|address| !HEAP.contains(address)),
+                )
+                .map(|address| (address, ram[address]))
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,199 @@
+use super::assembler::*;
+use std::collections::BTreeMap;
+
+/// A public interface for turning Hack machine code back into Hack assembly code.
+pub trait DisassemblerPublic {
+    /// Creates a new instance of the disassembler for the given machine code.
+    ///
+    /// # Arguments
+    ///
+    /// * `hack` - The machine code in the `.hack` text format, one 16-bit binary word per line.
+    ///
+    /// # Errors
+    ///
+    /// Errors will occur if a line is not a 16-bit binary word.
+    fn new(hack: &str) -> Self;
+
+    /// Loads the symbols to re-attach to the disassembled code.
+    ///
+    /// # Arguments
+    ///
+    /// * `symbols` - A symbol table in the `.sym` text format written by `AssemblerClass::to_symbols`.
+    ///
+    /// # Errors
+    ///
+    /// Errors will occur if a line is not a `label` or `variable` entry.
+    fn load_symbols(&mut self, symbols: &str);
+
+    /// Disassembles every word, one instruction per line.
+    ///
+    /// Without symbols every A-instruction is written as `@value`. With symbols, labels are
+    /// written before the instruction they point to and A-instructions are written with a
+    /// symbol when the next instruction shows how the value is used:
+    ///
+    /// * a label when the next instruction jumps, or loads the value with `D=A` (return addresses),
+    /// * a variable, or `SP`/`LCL`/`ARG`/`THIS`/`THAT`, when the next instruction accesses `M`.
+    fn disassemble(&self) -> String;
+}
+
+/// Represents a Hack disassembler.
+#[derive(Debug, Default)]
+pub struct DisassemblerClass {
+    /// The machine code, indexed by ROM address.
+    pub rom: Vec<u16>,
+
+    /// The labels pointing to each ROM address.
+    pub labels: BTreeMap<u16, Vec<String>>,
+
+    /// The variable stored at each RAM address.
+    pub variables: BTreeMap<u16, String>,
+}
+
+impl DisassemblerPublic for DisassemblerClass {
+    fn new(hack: &str) -> Self {
+        let rom = hack
+            .lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(number, line)| {
+                let line = line.trim();
+                Some(line)
+                    .filter(|line| line.len() == 16)
+                    .and_then(|line| u16::from_str_radix(line, 2).ok())
+                    .unwrap_or_else(|| panic!("Line {}: invalid word {line:?}", number + 1))
+            })
+            .collect();
+
+        DisassemblerClass {
+            rom,
+            ..Default::default()
+        }
+    }
+
+    fn load_symbols(&mut self, symbols: &str) {
+        for (number, line) in symbols.lines().enumerate() {
+            let parts: Vec<&str> = line.split_whitespace().collect();
+            match parts.as_slice() {
+                [] => continue,
+                ["label", name, address] if address.parse::<u16>().is_ok() => {
+                    let address = address.parse().unwrap();
+                    self.labels
+                        .entry(address)
+                        .or_default()
+                        .push(name.to_string());
+                }
+                ["variable", name, address] if address.parse::<u16>().is_ok() => {
+                    self.variables
+                        .insert(address.parse().unwrap(), name.to_string());
+                }
+                _ => panic!("Line {}: invalid symbol {line:?}", number + 1),
+            }
+        }
+    }
+
+    fn disassemble(&self) -> String {
+        let mut output = String::new();
+
+        for (address, word) in self.rom.iter().enumerate() {
+            for label in self.labels.get(&(address as u16)).into_iter().flatten() {
+                output.push_str(&format!("({label})\n"));
+            }
+            let line = match word & 0x8000 {
+                0 => format!("@{}", self.symbol(*word, self.rom.get(address + 1))),
+                _ => decode(*word).unwrap_or_else(|| format!("// unknown {word:016b}")),
+            };
+            output.push_str(&line);
+            output.push('\n');
+        }
+        // Labels can also point right after the last instruction.
+        for label in self
+            .labels
+            .get(&(self.rom.len() as u16))
+            .into_iter()
+            .flatten()
+        {
+            output.push_str(&format!("({label})\n"));
+        }
+        output
+    }
+}
+
+impl DisassemblerClass {
+    /// Returns the symbol to write for the value of an A-instruction followed by `next`.
+    fn symbol(&self, value: u16, next: Option<&u16>) -> String {
+        let Some(next) = next.filter(|next| *next & 0x8000 != 0) else {
+            return value.to_string();
+        };
+        let jumps = next & 0b111 != 0;
+        let loads_address = decode(*next).as_deref() == Some("D=A");
+        let uses_memory = next & 0x1000 != 0 || next & 0b001000 != 0;
+
+        if jumps || (loads_address && value > 15) {
+            if let Some(labels) = self.labels.get(&value) {
+                return labels[0].clone();
+            }
+        }
+        if uses_memory {
+            if let Some(variable) = self.variables.get(&value) {
+                return variable.clone();
+            }
+            if let Some(register) = ["SP", "LCL", "ARG", "THIS", "THAT"]
+                .into_iter()
+                .find(|register| predefined_symbol(register) == Some(value))
+            {
+                return register.to_string();
+            }
+        }
+        value.to_string()
+    }
+}
+
+/// Decodes a C-instruction into `dest=comp;jump`.
+///
+/// # Returns
+///
+/// `None` if the computation is not one of the 28 Hack computations.
+pub fn decode(word: u16) -> Option<String> {
+    let register = if word & 0x1000 != 0 { "M" } else { "A" };
+    let comp = match (word >> 6) & 0x3f {
+        0b101010 => "0",
+        0b111111 => "1",
+        0b111010 => "-1",
+        0b001100 => "D",
+        0b110000 => "A",
+        0b001101 => "!D",
+        0b110001 => "!A",
+        0b001111 => "-D",
+        0b110011 => "-A",
+        0b011111 => "D+1",
+        0b110111 => "A+1",
+        0b001110 => "D-1",
+        0b110010 => "A-1",
+        0b000010 => "D+A",
+        0b010011 => "D-A",
+        0b000111 => "A-D",
+        0b000000 => "D&A",
+        0b010101 => "D|A",
+        _ => return None,
+    }
+    .replace('A', register);
+
+    let dest: String = [(0b100000, 'A'), (0b001000, 'M'), (0b010000, 'D')]
+        .into_iter()
+        .filter(|(bit, _)| word & bit != 0)
+        .map(|(_, register)| register)
+        .collect();
+    let jump = ["", "JGT", "JEQ", "JGE", "JLT", "JNE", "JLE", "JMP"][(word & 0b111) as usize];
+
+    let mut instruction = String::new();
+    if !dest.is_empty() {
+        instruction.push_str(&dest);
+        instruction.push('=');
+    }
+    instruction.push_str(&comp);
+    if !jump.is_empty() {
+        instruction.push(';');
+        instruction.push_str(jump);
+    }
+    Some(instruction)
+}
@@ -0,0 +1,148 @@
+/// The number of words of the Hack data memory (RAM, screen and keyboard).
+pub const RAM_SIZE: usize = 32768;
+
+/// The instruction `0;JMP`, used to recognise the end-of-program loop.
+const JUMP: u16 = 0b1110_1010_1000_0111;
+
+/// A public interface for executing Hack machine code.
+pub trait EmulatorPublic {
+    /// Creates a new instance of the emulator with the program loaded in ROM and a cleared RAM.
+    ///
+    /// # Arguments
+    ///
+    /// * `rom` - The machine code, as produced by `AssemblerClass`.
+    fn new(rom: Vec<u16>) -> Self;
+
+    /// Executes the instruction at `pc`.
+    ///
+    /// # Errors
+    ///
+    /// Errors will occur if the emulator is halted.
+    fn step(&mut self);
+
+    /// Executes instructions until the program halts or `max_cycles` instructions have been executed.
+    ///
+    /// # Returns
+    ///
+    /// The number of executed instructions.
+    fn run(&mut self, max_cycles: usize) -> usize;
+
+    /// Checks if the program has stopped.
+    ///
+    /// # Returns
+    ///
+    /// `true` if `pc` is outside of the program or on an `@n; 0;JMP` loop jumping to itself, `false` otherwise.
+    fn is_halted(&self) -> bool;
+}
+
+/// Represents a Hack computer: the CPU registers, the data memory and the instruction memory.
+pub struct EmulatorClass {
+    /// The instruction memory.
+    pub rom: Vec<u16>,
+
+    /// The data memory.
+    pub ram: Vec<i16>,
+
+    /// The A register.
+    pub a: i16,
+
+    /// The D register.
+    pub d: i16,
+
+    /// The program counter.
+    pub pc: usize,
+
+    /// The number of instructions executed so far.
+    pub cycles: usize,
+}
+
+impl EmulatorPublic for EmulatorClass {
+    fn new(rom: Vec<u16>) -> Self {
+        EmulatorClass {
+            rom,
+            ram: vec![0; RAM_SIZE],
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+        }
+    }
+
+    fn step(&mut self) {
+        let instruction = *self
+            .rom
+            .get(self.pc)
+            .unwrap_or_else(|| panic!("PC {} is outside of the program", self.pc));
+        self.cycles += 1;
+
+        // A-instruction: load the value into A.
+        if instruction & 0x8000 == 0 {
+            self.a = instruction as i16;
+            self.pc += 1;
+            return;
+        }
+
+        // C-instruction: compute, store into the destinations and jump.
+        let address = (self.a as u16 & 0x7fff) as usize;
+        let y = if instruction & 0x1000 != 0 {
+            self.ram[address]
+        } else {
+            self.a
+        };
+        let out = alu(self.d, y, (instruction >> 6) & 0x3f);
+
+        if instruction & 0b001000 != 0 {
+            self.ram[address] = out;
+        }
+        let jump_to = self.a as u16 as usize;
+        if instruction & 0b100000 != 0 {
+            self.a = out;
+        }
+        if instruction & 0b010000 != 0 {
+            self.d = out;
+        }
+
+        let jump = instruction & 0b111;
+        let taken = (jump & 0b100 != 0 && out < 0)
+            || (jump & 0b010 != 0 && out == 0)
+            || (jump & 0b001 != 0 && out > 0);
+        self.pc = if taken { jump_to } else { self.pc + 1 };
+    }
+
+    fn run(&mut self, max_cycles: usize) -> usize {
+        let start = self.cycles;
+        while self.cycles - start < max_cycles && !self.is_halted() {
+            self.step();
+        }
+        self.cycles - start
+    }
+
+    fn is_halted(&self) -> bool {
+        if self.pc >= self.rom.len() {
+            return true;
+        }
+        self.rom[self.pc] as usize == self.pc && self.rom.get(self.pc + 1) == Some(&JUMP)
+    }
+}
+
+/// Computes the Hack ALU function selected by the six `c` bits (`zx nx zy ny f no`).
+fn alu(x: i16, y: i16, control: u16) -> i16 {
+    let mut x = if control & 0b100000 != 0 { 0 } else { x };
+    if control & 0b010000 != 0 {
+        x = !x;
+    }
+    let mut y = if control & 0b001000 != 0 { 0 } else { y };
+    if control & 0b000100 != 0 {
+        y = !y;
+    }
+    let out = if control & 0b000010 != 0 {
+        x.wrapping_add(y)
+    } else {
+        x & y
+    };
+    if control & 0b000001 != 0 {
+        !out
+    } else {
+        out
+    }
+}
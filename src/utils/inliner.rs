@@ -0,0 +1,297 @@
+use super::program::*;
+use super::stack_checker::*;
+use crate::prelude::*;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// The default maximum number of commands in the body of a function to inline.
+pub const INLINE_THRESHOLD: usize = 8;
+
+/// A public interface for inlining small functions at their call sites.
+pub trait InlinerPublic {
+    /// Creates a new instance of the inliner.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to optimise.
+    /// * `threshold` - The maximum number of commands in the body of an inlined function, `function` excluded.
+    fn new(program: &ProgramClass, threshold: usize) -> Self;
+
+    /// Replaces the calls to small leaf functions by their body.
+    ///
+    /// A function is inlined when its body has at most `threshold` commands, calls no function,
+    /// ends with `return` and leaves exactly one value on its working stack at every `return`.
+    /// At each call site the arguments and the local variables of the callee are moved to extra
+    /// local variables of the caller, its labels are renamed, its `return` commands jump to the
+    /// end of the body and the `this`/`that` base addresses of the caller are restored when the
+    /// callee sets them. Calls outside of any function are kept, as they have no local variables.
+    ///
+    /// # Returns
+    ///
+    /// The optimised program.
+    fn inline(&mut self) -> ProgramClass;
+
+    /// Formats the number of inlined call sites of every function.
+    fn report(&self) -> String;
+}
+
+/// Represents a function that can be inlined.
+#[derive(Clone, Debug)]
+struct Candidate {
+    /// The function.
+    function: Function,
+    /// The number of arguments the body uses: one past the highest `argument` index.
+    n_args: usize,
+    /// The `pointer` indexes the body pops into.
+    pointers: BTreeSet<i32>,
+    /// `true` when a `return` other than the last one needs a jump to the end of the body.
+    early_return: bool,
+}
+
+/// Represents the inlining pass over a VM instruction stream.
+#[derive(Clone, Debug, Default)]
+pub struct InlinerClass {
+    /// The program to optimise.
+    pub program: ProgramClass,
+
+    /// The maximum number of commands in the body of an inlined function.
+    pub threshold: usize,
+
+    /// The number of call sites inlined by the last `inline`, per inlined function.
+    pub sites: BTreeMap<String, usize>,
+}
+
+impl InlinerPublic for InlinerClass {
+    fn new(program: &ProgramClass, threshold: usize) -> Self {
+        InlinerClass {
+            program: program.clone(),
+            threshold,
+            sites: BTreeMap::new(),
+        }
+    }
+
+    fn inline(&mut self) -> ProgramClass {
+        let instructions = &self.program.instructions;
+        let candidates = self.candidates();
+        let mut output: Vec<Instruction> = Vec::new();
+        // The `function` command of the caller being copied and its number of local variables.
+        let mut caller: Option<(usize, usize)> = None;
+        // The number of local variables each caller needs for its inlined calls.
+        let mut extra: HashMap<usize, usize> = HashMap::new();
+        self.sites.clear();
+
+        for instruction in instructions {
+            if instruction.name() == "function" {
+                let n_vars = instruction.part(2).and_then(|n| n.parse().ok());
+                caller = n_vars.map(|n_vars| (output.len(), n_vars));
+            }
+            let callee = instruction.part(1).unwrap_or_default();
+            let n_args: usize = instruction
+                .part(2)
+                .and_then(|n| n.parse().ok())
+                .unwrap_or(0);
+            let site = match (instruction.name().as_str(), caller, candidates.get(callee)) {
+                ("call", Some(caller), Some(candidate)) if candidate.n_args <= n_args => {
+                    Some((caller, candidate))
+                }
+                _ => None,
+            };
+            let Some(((position, n_vars), candidate)) = site else {
+                output.push(instruction.clone());
+                continue;
+            };
+
+            let number: usize = self.sites.values().sum();
+            let needed = self.expand(candidate, instruction, n_args, n_vars, number, &mut output);
+            let slots = extra.entry(position).or_default();
+            *slots = (*slots).max(needed);
+            *self
+                .sites
+                .entry(candidate.function.name.clone())
+                .or_default() += 1;
+        }
+
+        // Give every caller the local variables its inlined calls use.
+        for (position, slots) in extra {
+            let function = &mut output[position];
+            let name = function.part(1).unwrap_or_default().to_string();
+            let n_vars: usize = function.part(2).unwrap().parse().unwrap();
+            function.current_command = format!("function {name} {}", n_vars + slots);
+        }
+        ProgramClass {
+            instructions: output,
+        }
+    }
+
+    fn report(&self) -> String {
+        let mut output = format!("{:<32} {:>8}\n", "Inlined function", "Sites");
+        for (name, sites) in &self.sites {
+            output.push_str(&format!("{name:<32} {sites:>8}\n"));
+        }
+        output.push_str(&format!(
+            "{:<32} {:>8}\n",
+            "Total",
+            self.sites.values().sum::<usize>()
+        ));
+        output
+    }
+}
+
+impl InlinerClass {
+    /// Returns the functions that can be inlined, keyed by name.
+    fn candidates(&self) -> HashMap<String, Candidate> {
+        let instructions = &self.program.instructions;
+        let mut checker = StackCheckerClass::new(&self.program);
+        checker.check();
+
+        let mut candidates: HashMap<String, Candidate> = HashMap::new();
+        let mut declared: BTreeSet<String> = BTreeSet::new();
+        for function in self.program.functions() {
+            // Functions declared twice are left alone, the symbol checks report them.
+            if !declared.insert(function.name.clone()) {
+                candidates.remove(&function.name);
+                continue;
+            }
+            let body = &instructions[function.start + 1..function.end];
+            if body.is_empty()
+                || body.len() > self.threshold
+                || body.last().unwrap().name() != "return"
+            {
+                continue;
+            }
+
+            let labels: BTreeSet<&str> = body
+                .iter()
+                .filter(|instruction| instruction.name() == "label")
+                .filter_map(|instruction| instruction.part(1))
+                .collect();
+            let mut n_args = 0;
+            let mut pointers: BTreeSet<i32> = BTreeSet::new();
+            let mut returns = 0;
+            let mut inlinable = true;
+            for (offset, instruction) in body.iter().enumerate() {
+                let depth = checker.depths[function.start + 1 + offset];
+                let segment = instruction.part(1).unwrap_or_default();
+                let index = instruction.index.unwrap_or(0);
+                inlinable &= match instruction.name().as_str() {
+                    "call" | "function" => false,
+                    "goto" | "if-goto" => labels.contains(segment),
+                    "return" => {
+                        returns += 1;
+                        depth.is_none_or(|depth| depth == 1)
+                    }
+                    "push" | "pop" if segment == "local" => {
+                        index >= 0 && (index as usize) < function.n_vars
+                    }
+                    "push" | "pop" if segment == "argument" => {
+                        n_args = n_args.max(index as usize + 1);
+                        index >= 0
+                    }
+                    _ => true,
+                };
+                if instruction.name() == "pop" && segment == "pointer" {
+                    pointers.insert(index);
+                }
+                // The body must never pop the values of the caller.
+                inlinable &= depth.is_none_or(|depth| stack_effect(instruction).0 <= depth);
+            }
+            if inlinable {
+                candidates.insert(
+                    function.name.clone(),
+                    Candidate {
+                        function,
+                        n_args,
+                        pointers,
+                        early_return: returns > 1,
+                    },
+                );
+            }
+        }
+        candidates
+    }
+
+    /// Writes the body of `candidate` in place of the `call`, using the local variables of the
+    /// caller from `base` on, and returns the number of local variables used.
+    fn expand(
+        &self,
+        candidate: &Candidate,
+        call: &Instruction,
+        n_args: usize,
+        base: usize,
+        number: usize,
+        output: &mut Vec<Instruction>,
+    ) -> usize {
+        let function = &candidate.function;
+        let vars = base + n_args;
+        let saved = vars + function.n_vars;
+        let end = format!("{}.inline.{number}", function.name);
+        let rename = |label: &str| format!("{label}.inline.{number}");
+
+        // Move the arguments from the stack, the last one being on top.
+        for index in (0..n_args).rev() {
+            output.push(command(call, "pop", "local", base + index));
+        }
+        for index in 0..function.n_vars {
+            output.push(command(call, "push", "constant", 0));
+            output.push(command(call, "pop", "local", vars + index));
+        }
+        for (slot, pointer) in candidate.pointers.iter().enumerate() {
+            output.push(command(call, "push", "pointer", *pointer as usize));
+            output.push(command(call, "pop", "local", saved + slot));
+        }
+
+        let body = &self.program.instructions[function.start + 1..function.end];
+        for (offset, instruction) in body.iter().enumerate() {
+            let name = instruction.name();
+            let segment = instruction.part(1).unwrap_or_default();
+            let index = instruction.index.unwrap_or(0) as usize;
+            output.push(match name.as_str() {
+                "push" | "pop" if segment == "argument" => {
+                    command(instruction, &name, "local", base + index)
+                }
+                "push" | "pop" if segment == "local" => {
+                    command(instruction, &name, "local", vars + index)
+                }
+                "label" | "goto" | "if-goto" => Instruction {
+                    current_command: format!("{name} {}", rename(segment)),
+                    ..instruction.clone()
+                },
+                "return" if offset + 1 == body.len() => break,
+                "return" => Instruction {
+                    current_command: format!("goto {end}"),
+                    command_type: Some(Command::Branch("goto".to_string())),
+                    ..instruction.clone()
+                },
+                _ => instruction.clone(),
+            });
+        }
+
+        let last = body.last().unwrap();
+        if candidate.early_return {
+            output.push(Instruction {
+                current_command: format!("label {end}"),
+                command_type: Some(Command::Branch("label".to_string())),
+                ..last.clone()
+            });
+        }
+        for (slot, pointer) in candidate.pointers.iter().enumerate() {
+            output.push(command(last, "push", "local", saved + slot));
+            output.push(command(last, "pop", "pointer", *pointer as usize));
+        }
+        n_args + function.n_vars + candidate.pointers.len()
+    }
+}
+
+/// Returns the `push` or `pop` command of `segment` and `index`, located at `origin`.
+fn command(origin: &Instruction, name: &str, segment: &str, index: usize) -> Instruction {
+    let segment_type = match segment {
+        "local" | "argument" | "this" | "that" => Segment::Internal(segment.to_string()),
+        _ => Segment::External(segment.to_string()),
+    };
+    Instruction {
+        current_command: format!("{name} {segment} {index}"),
+        command_type: Some(Command::PushPop(name.to_string())),
+        segment_type: Some(segment_type),
+        index: Some(index as i32),
+        ..origin.clone()
+    }
+}
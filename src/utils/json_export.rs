@@ -0,0 +1,97 @@
+use super::call_graph::*;
+use super::code_writer::*;
+use super::program::*;
+use super::rom_usage::*;
+use super::stack_usage::*;
+use super::statics::*;
+use crate::prelude::*;
+use serde::Serialize;
+
+/// A public interface for exporting a translated program and its analyses as JSON.
+pub trait JsonExportPublic {
+    /// Collects the instructions, diagnostics, call graph, statistics and source map of a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The translated program.
+    /// * `writer` - The code writer the program was translated with, holding its source map and ROM usage.
+    /// * `diagnostics` - The problems found in the program.
+    /// * `entry` - The entry point of the call graph.
+    fn new(
+        program: &ProgramClass,
+        writer: &CodeWriterClass,
+        diagnostics: Vec<Diagnostic>,
+        entry: &str,
+    ) -> Self;
+
+    /// Formats the export as pretty-printed JSON.
+    fn to_json(&self) -> String;
+}
+
+/// Represents the memory statistics of a translated program.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Statistics {
+    /// The ROM words taken per VM opcode, function and file.
+    pub rom_usage: RomUsageClass,
+
+    /// The allocation of the static variables.
+    pub statics: StaticsClass,
+
+    /// The worst-case stack usage of every function.
+    pub stack_usage: StackUsageClass,
+
+    /// The worst-case stack usage from the entry point, `None` when unbounded.
+    pub stack_bound: Option<usize>,
+}
+
+/// Represents everything known about a translated program, in a serde-serialisable form.
+///
+/// Every instruction and diagnostic carries its span: the VM file and the 1-based line it comes from.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct JsonExportClass {
+    /// The entry point of the call graph.
+    pub entry: String,
+
+    /// The parsed instruction stream, in translation order.
+    pub instructions: Vec<Instruction>,
+
+    /// The problems found in the program, sorted by location.
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// The declared functions and the functions each of them calls.
+    pub call_graph: CallGraphClass,
+
+    /// The memory statistics of the program.
+    pub statistics: Statistics,
+
+    /// The ROM address of every VM command, the bootstrap code first.
+    pub source_map: Vec<SourceMapEntry>,
+}
+
+impl JsonExportPublic for JsonExportClass {
+    fn new(
+        program: &ProgramClass,
+        writer: &CodeWriterClass,
+        diagnostics: Vec<Diagnostic>,
+        entry: &str,
+    ) -> Self {
+        let stack_usage = StackUsageClass::new(program);
+        JsonExportClass {
+            entry: entry.to_string(),
+            instructions: program.instructions.clone(),
+            diagnostics,
+            call_graph: CallGraphClass::new(program),
+            statistics: Statistics {
+                rom_usage: writer.rom_usage.clone(),
+                statics: StaticsClass::new(program),
+                stack_bound: stack_usage.bound(entry),
+                stack_usage,
+            },
+            source_map: writer.source_map.clone(),
+        }
+    }
+
+    fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("Cannot serialise the program")
+    }
+}
@@ -0,0 +1,276 @@
+use super::assembly::*;
+use super::code_writer::is_instruction;
+use std::collections::{HashMap, HashSet};
+
+/// A public interface for cleaning up the jumps of the generated assembly.
+pub trait JumpThreadingPublic {
+    /// Creates a new instance of the pass.
+    ///
+    /// # Arguments
+    ///
+    /// * `assembly` - The generated assembly to optimise.
+    fn new(assembly: &AssemblyClass) -> Self;
+
+    /// Rewrites the `@label` / `comp;jump` pairs of the assembly until none of the rules applies:
+    ///
+    /// * a jump to a label followed by `@other` / `0;JMP` jumps to `other` directly;
+    /// * `@l1` / `D;Jcc` followed by `@l2` / `0;JMP` and `(l1)` becomes `@l2` / `D;J!cc`;
+    /// * a jump to the labels right after it is removed.
+    ///
+    /// Only pairs whose jump has no destination and computes from neither A nor M are touched,
+    /// so moving or removing them leaves D and the memory unchanged. A may then hold another
+    /// address, which is only correct because no template reads A, after a label or after a
+    /// jump it falls through, before setting it. Labels and comments are kept, and every block
+    /// still stands for the VM command of its entry.
+    ///
+    /// # Returns
+    ///
+    /// The optimised assembly.
+    fn thread(&mut self) -> AssemblyClass;
+}
+
+/// Represents the jump threading pass over the generated assembly.
+#[derive(Clone, Debug, Default)]
+pub struct JumpThreadingClass {
+    /// The generated assembly to optimise.
+    pub assembly: AssemblyClass,
+
+    /// The number of jumps retargeted past a chain of jumps by the last `thread`.
+    pub threaded: usize,
+
+    /// The number of conditional jumps inverted over an unconditional one by the last `thread`.
+    pub inverted: usize,
+
+    /// The number of jumps to the next instruction removed by the last `thread`.
+    pub removed: usize,
+}
+
+/// Represents a line of assembly code, located by its block and line indexes.
+#[derive(Clone, Debug, PartialEq)]
+enum Item {
+    /// A `(label)` declaration.
+    Label(String),
+    /// An `@target` instruction followed by a `comp;jump` instruction, at `jump`.
+    Jump {
+        target: String,
+        comp: String,
+        condition: String,
+        jump: (usize, usize),
+    },
+    /// Any other instruction.
+    Other,
+}
+
+impl JumpThreadingPublic for JumpThreadingClass {
+    fn new(assembly: &AssemblyClass) -> Self {
+        JumpThreadingClass {
+            assembly: assembly.clone(),
+            ..Default::default()
+        }
+    }
+
+    fn thread(&mut self) -> AssemblyClass {
+        let mut assembly = self.assembly.clone();
+        self.threaded = 0;
+        self.inverted = 0;
+        self.removed = 0;
+        while self.rewrite(&mut assembly) {}
+        assembly
+    }
+}
+
+impl JumpThreadingClass {
+    /// Applies every rule once over the assembly, and returns `true` when something changed.
+    fn rewrite(&mut self, assembly: &mut AssemblyClass) -> bool {
+        let items = items(assembly);
+        let labels: HashMap<&str, usize> = items
+            .iter()
+            .enumerate()
+            .filter_map(|(position, (_, item))| match item {
+                Item::Label(label) => Some((label.as_str(), position)),
+                _ => None,
+            })
+            .collect();
+        let mut replaced: Vec<((usize, usize), String)> = Vec::new();
+        let mut deleted: HashSet<(usize, usize)> = HashSet::new();
+
+        let mut position = 0;
+        while position < items.len() {
+            let (at, item) = &items[position];
+            let Item::Jump {
+                target,
+                comp,
+                condition,
+                jump,
+            } = item
+            else {
+                position += 1;
+                continue;
+            };
+
+            // A jump to the labels right after it does nothing.
+            if following_labels(&items, position + 1).contains(&target.as_str()) {
+                deleted.insert(*at);
+                deleted.insert(*jump);
+                self.removed += 1;
+                position += 1;
+                continue;
+            }
+
+            // A conditional jump over an unconditional one jumps where the latter goes, on the opposite condition.
+            if let (
+                Some(inverse),
+                Some((
+                    next_at,
+                    Item::Jump {
+                        target: other,
+                        condition: always,
+                        jump: next_jump,
+                        ..
+                    },
+                )),
+            ) = (inverse(condition), items.get(position + 1))
+            {
+                if always == "JMP"
+                    && following_labels(&items, position + 2).contains(&target.as_str())
+                {
+                    replaced.push((*at, format!("@{other}")));
+                    replaced.push((*jump, format!("{comp};{inverse}")));
+                    deleted.insert(*next_at);
+                    deleted.insert(*next_jump);
+                    self.inverted += 1;
+                    position += 2;
+                    continue;
+                }
+            }
+
+            // A jump to a jump goes to the end of the chain.
+            let end = resolve(&items, &labels, target);
+            if end != *target {
+                replaced.push((*at, format!("@{end}")));
+                self.threaded += 1;
+            }
+            position += 1;
+        }
+
+        let changed = !replaced.is_empty() || !deleted.is_empty();
+        for ((block, line), text) in replaced {
+            assembly.blocks[block].lines[line] = text;
+        }
+        for (number, block) in assembly.blocks.iter_mut().enumerate() {
+            let mut line = 0;
+            block.lines.retain(|_| {
+                line += 1;
+                !deleted.contains(&(number, line - 1))
+            });
+        }
+        changed
+    }
+}
+
+/// Returns the labels and instructions of the assembly with their location, comments excluded.
+fn items(assembly: &AssemblyClass) -> Vec<((usize, usize), Item)> {
+    let lines: Vec<((usize, usize), &str)> = assembly
+        .blocks
+        .iter()
+        .enumerate()
+        .flat_map(|(number, block)| {
+            block
+                .lines
+                .iter()
+                .enumerate()
+                .map(move |(line, text)| ((number, line), text.trim()))
+        })
+        .filter(|(_, text)| text.starts_with('(') || is_instruction(text))
+        .collect();
+
+    let mut items = Vec::new();
+    let mut position = 0;
+    while position < lines.len() {
+        let (at, text) = lines[position];
+        if let Some(label) = text
+            .strip_prefix('(')
+            .and_then(|text| text.strip_suffix(')'))
+        {
+            items.push((at, Item::Label(label.to_string())));
+            position += 1;
+            continue;
+        }
+        let jump = lines.get(position + 1).and_then(|(jump, next)| {
+            let (comp, condition) = next.split_once(';')?;
+            let movable = !comp.contains(['=', 'A', 'M']);
+            movable.then(|| (*jump, comp.to_string(), condition.to_string()))
+        });
+        match (text.strip_prefix('@'), jump) {
+            (Some(target), Some((jump, comp, condition))) => {
+                items.push((
+                    at,
+                    Item::Jump {
+                        target: target.to_string(),
+                        comp,
+                        condition,
+                        jump,
+                    },
+                ));
+                position += 2;
+            }
+            _ => {
+                items.push((at, Item::Other));
+                position += 1;
+            }
+        }
+    }
+    items
+}
+
+/// Returns the labels declared from `position` on, up to the next instruction.
+fn following_labels(items: &[((usize, usize), Item)], position: usize) -> Vec<&str> {
+    items[position.min(items.len())..]
+        .iter()
+        .map_while(|(_, item)| match item {
+            Item::Label(label) => Some(label.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the label a jump to `label` ends up at, following the unconditional jumps placed right after labels.
+fn resolve(items: &[((usize, usize), Item)], labels: &HashMap<&str, usize>, label: &str) -> String {
+    let mut label = label.to_string();
+    let mut visited: HashSet<String> = HashSet::from([label.clone()]);
+    while let Some(position) = labels.get(label.as_str()) {
+        let next = items[*position..]
+            .iter()
+            .find(|(_, item)| !matches!(item, Item::Label(_)));
+        let Some((
+            _,
+            Item::Jump {
+                target, condition, ..
+            },
+        )) = next
+        else {
+            break;
+        };
+        if condition != "JMP"
+            || !labels.contains_key(target.as_str())
+            || !visited.insert(target.clone())
+        {
+            break;
+        }
+        label = target.clone();
+    }
+    label
+}
+
+/// Returns the jump taken exactly when `condition` is not, `None` for an unconditional jump.
+fn inverse(condition: &str) -> Option<&'static str> {
+    match condition {
+        "JEQ" => Some("JNE"),
+        "JNE" => Some("JEQ"),
+        "JGT" => Some("JLE"),
+        "JLE" => Some("JGT"),
+        "JLT" => Some("JGE"),
+        "JGE" => Some("JLT"),
+        _ => None,
+    }
+}
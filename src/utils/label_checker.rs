@@ -0,0 +1,158 @@
+use super::assembler::*;
+use super::program::*;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// A public interface for checking the labels of every function.
+pub trait LabelCheckerPublic {
+    /// Creates a new instance of the label checker.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to check.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Checks the `label`, `goto` and `if-goto` commands of every function (and of the code
+    /// before the first function).
+    ///
+    /// Labels belong to the function declaring them, and `CodeWriterClass` writes them as
+    /// `<function>$<label>`, so functions may declare the same labels. The labels of the code
+    /// before the first function are written as they are, so they must not clash with the
+    /// symbols the writer and the assembler define.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Diagnostic>` sorted by location, with an error for every jump to an undeclared
+    /// label or to a label of another function, every label declared twice in a function and
+    /// every label before the first function clashing with a generated or predefined symbol.
+    fn check(&self) -> Vec<Diagnostic>;
+}
+
+/// Represents a check of the labels of a program.
+#[derive(Clone, Debug, Default)]
+pub struct LabelCheckerClass {
+    /// The instructions of the program.
+    pub instructions: Vec<Instruction>,
+
+    /// The functions of the program.
+    pub functions: Vec<Function>,
+}
+
+impl LabelCheckerPublic for LabelCheckerClass {
+    fn new(program: &ProgramClass) -> Self {
+        LabelCheckerClass {
+            instructions: program.instructions.clone(),
+            functions: program.functions(),
+        }
+    }
+
+    fn check(&self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        let owner = self.owners();
+
+        // The first declaration of every label in each function, and the first function declaring it.
+        let mut scoped: HashMap<(Option<usize>, &str), usize> = HashMap::new();
+        let mut labels: HashMap<&str, usize> = HashMap::new();
+        for (position, instruction) in self.instructions.iter().enumerate() {
+            if instruction.name() != "label" {
+                continue;
+            }
+            let Some(label) = instruction.part(1) else {
+                continue;
+            };
+            labels.entry(label).or_insert(position);
+            if let Some(declared) = scoped.get(&(owner[position], label)) {
+                let first = &self.instructions[*declared];
+                let message = format!(
+                    "label {label} is already declared at {}:{}",
+                    first.file_name, first.line
+                );
+                diagnostics.push(Diagnostic::new(Severity::Error, instruction, message));
+            } else {
+                scoped.insert((owner[position], label), position);
+            }
+            if owner[position].is_some() {
+                continue;
+            }
+            if let Some(symbol) = self.clash(label) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    instruction,
+                    format!("label {label} clashes with the {symbol}"),
+                ));
+            }
+        }
+
+        for (position, instruction) in self.instructions.iter().enumerate() {
+            if !matches!(instruction.name().as_str(), "goto" | "if-goto") {
+                continue;
+            }
+            let label = instruction.part(1).unwrap_or_default();
+            let own = owner[position];
+            if scoped.contains_key(&(own, label)) {
+                continue;
+            }
+            let message = match labels.get(label) {
+                Some(target) => format!(
+                    "{} jumps into {}, labels can only be used in the function declaring them",
+                    instruction.current_command,
+                    self.function_name(owner[*target])
+                ),
+                None => format!(
+                    "label {label} is not declared in {}",
+                    self.function_name(own)
+                ),
+            };
+            diagnostics.push(Diagnostic::new(Severity::Error, instruction, message));
+        }
+
+        diagnostics.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+        diagnostics
+    }
+}
+
+impl LabelCheckerClass {
+    /// Returns the index in `functions` of the function each instruction belongs to,
+    /// `None` for the code before the first function.
+    fn owners(&self) -> Vec<Option<usize>> {
+        let mut owner = vec![None; self.instructions.len()];
+        for (index, function) in self.functions.iter().enumerate() {
+            owner[function.start..function.end].fill(Some(index));
+        }
+        owner
+    }
+
+    fn function_name(&self, index: Option<usize>) -> String {
+        match index {
+            Some(index) => self.functions[index].name.clone(),
+            None => "the code before the first function".to_string(),
+        }
+    }
+
+    /// Describes the symbol written by the translator or defined by the assembler that `label` clashes with.
+    fn clash(&self, label: &str) -> Option<String> {
+        let numbered = |prefix: &str| {
+            label
+                .strip_prefix(prefix)
+                .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+        };
+        if numbered("CON_TRUE_") || numbered("CON_FINISH_") {
+            return Some("labels generated for comparisons".to_string());
+        }
+        if label == "returnAddress.0" || label == "while" {
+            return Some("labels of the bootstrap code".to_string());
+        }
+        if let Some((_, n)) = label.rsplit_once(".ret.") {
+            if !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()) {
+                return Some("return addresses generated for calls".to_string());
+            }
+        }
+        if self.functions.iter().any(|function| function.name == label) {
+            return Some(format!("entry point of function {label}"));
+        }
+        if predefined_symbol(label).is_some() {
+            return Some(format!("predefined symbol {label}"));
+        }
+        None
+    }
+}
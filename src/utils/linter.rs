@@ -0,0 +1,284 @@
+use super::cfg::*;
+use super::program::*;
+use crate::prelude::*;
+use std::collections::{BTreeSet, HashSet};
+
+/// The lint rules, all enabled by default.
+pub const RULES: [&str; 5] = [
+    "unreachable-code",
+    "unused-label",
+    "missing-return",
+    "push-pop-noop",
+    "unused-pointer",
+];
+
+/// A public interface for finding suspicious but valid VM code.
+pub trait LinterPublic {
+    /// Creates a new instance of the linter with every rule enabled.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to lint.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Enables or disables a rule.
+    ///
+    /// # Arguments
+    ///
+    /// * `rule` - One of `RULES`, or `all`.
+    /// * `enabled` - `true` to enable the rule.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error message if the rule does not exist.
+    fn set_rule(&mut self, rule: &str, enabled: bool) -> Result<(), String>;
+
+    /// Runs the enabled rules on every function (and on the code before the first function):
+    ///
+    /// * `unreachable-code` - commands no path reaches, e.g. after `goto` or `return`,
+    /// * `unused-label` - labels no `goto` or `if-goto` of the function jumps to,
+    /// * `missing-return` - functions whose last command can be reached and is not `goto` or `return`,
+    /// * `push-pop-noop` - `push x i` directly followed by `pop x i`,
+    /// * `unused-pointer` - `pop pointer i` overwritten or discarded by `return` before `this`/`that` is used.
+    ///
+    /// A rule is not reported on a line containing a `// vm-allow(rule)` comment.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Diagnostic>` of warnings sorted by location.
+    fn check(&self) -> Vec<Diagnostic>;
+}
+
+/// Represents a configurable set of lint rules over a VM program.
+#[derive(Clone, Debug, Default)]
+pub struct LinterClass {
+    /// The program to lint.
+    pub program: ProgramClass,
+
+    /// The enabled rules.
+    pub rules: BTreeSet<String>,
+}
+
+impl LinterPublic for LinterClass {
+    fn new(program: &ProgramClass) -> Self {
+        LinterClass {
+            program: program.clone(),
+            rules: RULES.iter().map(|rule| rule.to_string()).collect(),
+        }
+    }
+
+    fn set_rule(&mut self, rule: &str, enabled: bool) -> Result<(), String> {
+        let rules: Vec<&str> = match rule {
+            "all" => RULES.to_vec(),
+            rule if RULES.contains(&rule) => vec![rule],
+            _ => {
+                return Err(format!(
+                    "Unknown lint rule {rule}, expected all or one of {}",
+                    RULES.join(", ")
+                ))
+            }
+        };
+        for rule in rules {
+            match enabled {
+                true => self.rules.insert(rule.to_string()),
+                false => self.rules.remove(rule),
+            };
+        }
+        Ok(())
+    }
+
+    fn check(&self) -> Vec<Diagnostic> {
+        let instructions = &self.program.instructions;
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+        // The code before the first function is linted like a function that needs no `return`.
+        let mut functions = self.program.functions();
+        let first = functions
+            .first()
+            .map_or(instructions.len(), |function| function.start);
+        if first > 0 {
+            functions.insert(
+                0,
+                Function {
+                    name: String::new(),
+                    file_name: instructions[0].file_name.clone(),
+                    n_vars: 0,
+                    start: 0,
+                    end: first,
+                },
+            );
+        }
+
+        for function in &functions {
+            let cfg = CfgClass::new(&self.program, function);
+            self.lint_blocks(&cfg, &mut diagnostics);
+            self.lint_commands(&cfg.instructions, &mut diagnostics);
+        }
+
+        diagnostics.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+        diagnostics
+    }
+}
+
+impl LinterClass {
+    /// Adds a warning of `rule` at `instruction`, unless the rule is disabled or allowed on the line.
+    fn report(
+        &self,
+        rule: &str,
+        instruction: &Instruction,
+        message: String,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        if !self.rules.contains(rule) || instruction.allow.iter().any(|allowed| allowed == rule) {
+            return;
+        }
+        let mut diagnostic = Diagnostic::new(Severity::Warning, instruction, message);
+        diagnostic.rule = Some(rule.to_string());
+        diagnostics.push(diagnostic);
+    }
+
+    /// Runs the rules about the control flow of a function.
+    fn lint_blocks(&self, cfg: &CfgClass, diagnostics: &mut Vec<Diagnostic>) {
+        if cfg.blocks.is_empty() {
+            return;
+        }
+
+        // Find the blocks reachable from the first one.
+        let mut reachable = vec![false; cfg.blocks.len()];
+        let mut worklist = vec![0];
+        while let Some(block) = worklist.pop() {
+            if std::mem::replace(&mut reachable[block], true) {
+                continue;
+            }
+            for edge in &cfg.blocks[block].edges {
+                match edge {
+                    Edge::FallThrough(next)
+                    | Edge::Goto(next)
+                    | Edge::Branch(next)
+                    | Edge::Call(_, next) => worklist.push(*next),
+                    Edge::Return => {}
+                }
+            }
+        }
+
+        // Report the first command of every run of unreachable blocks.
+        let mut in_run = false;
+        for (number, block) in cfg.blocks.iter().enumerate() {
+            let commands = &cfg.instructions[block.start..block.end];
+            if reachable[number] {
+                in_run = false;
+                continue;
+            }
+            let Some(first) = commands
+                .iter()
+                .find(|instruction| instruction.name() != "label")
+            else {
+                continue;
+            };
+            if !in_run {
+                self.report(
+                    "unreachable-code",
+                    first,
+                    "unreachable code".to_string(),
+                    diagnostics,
+                );
+                in_run = true;
+            }
+        }
+
+        // Report the labels no jump of the function uses.
+        let targets: HashSet<&str> = cfg
+            .instructions
+            .iter()
+            .filter(|instruction| matches!(instruction.name().as_str(), "goto" | "if-goto"))
+            .filter_map(|instruction| instruction.part(1))
+            .collect();
+        for instruction in &cfg.instructions {
+            let label = instruction.part(1).unwrap_or_default();
+            if instruction.name() == "label" && !targets.contains(label) {
+                self.report(
+                    "unused-label",
+                    instruction,
+                    format!("label {label} is never used"),
+                    diagnostics,
+                );
+            }
+        }
+
+        // Report functions whose last command can be reached and does not leave the function.
+        let last = cfg.instructions.last().unwrap();
+        if !cfg.function.is_empty()
+            && reachable[cfg.blocks.len() - 1]
+            && !matches!(last.name().as_str(), "goto" | "return")
+        {
+            self.report(
+                "missing-return",
+                last,
+                format!("{} can run past its end without `return`", cfg.function),
+                diagnostics,
+            );
+        }
+    }
+
+    /// Runs the rules about sequences of commands.
+    fn lint_commands(&self, instructions: &[Instruction], diagnostics: &mut Vec<Diagnostic>) {
+        for (position, instruction) in instructions.iter().enumerate() {
+            let next = instructions.get(position + 1);
+            if instruction.name() == "push"
+                && next.is_some_and(|next| {
+                    next.name() == "pop"
+                        && next.part(1) == instruction.part(1)
+                        && next.index == instruction.index
+                        && !next.allow.iter().any(|allowed| allowed == "push-pop-noop")
+                })
+            {
+                self.report(
+                    "push-pop-noop",
+                    instruction,
+                    format!(
+                        "`{}` followed by `{}` has no effect",
+                        instruction.current_command,
+                        next.unwrap().current_command
+                    ),
+                    diagnostics,
+                );
+            }
+
+            if instruction.name() == "pop"
+                && instruction.part(1) == Some("pointer")
+                && !pointer_used(instruction, &instructions[position + 1..])
+            {
+                let segment = ["this", "that"][usize::from(instruction.index == Some(1))];
+                self.report(
+                    "unused-pointer",
+                    instruction,
+                    format!("the base address of {segment} set here is never used"),
+                    diagnostics,
+                );
+            }
+        }
+    }
+}
+
+/// Returns `false` when the `pop pointer i` command is followed, without any jump or label in
+/// between, by another `pop pointer i` or a `return` before `this`/`that` is used.
+///
+/// Calls count as uses, as the called function starts with the same `this` and `that`.
+fn pointer_used(pop: &Instruction, rest: &[Instruction]) -> bool {
+    let segment = ["this", "that"][usize::from(pop.index == Some(1))];
+    for instruction in rest {
+        match instruction.name().as_str() {
+            "push" | "pop"
+                if instruction.part(1) == Some(segment)
+                    || (instruction.part(1) == Some("pointer")
+                        && instruction.index == pop.index) =>
+            {
+                return instruction.name() == "push" || instruction.part(1) == Some(segment);
+            }
+            "return" => return false,
+            "call" | "label" | "goto" | "if-goto" | "function" => return true,
+            _ => {}
+        }
+    }
+    true
+}
@@ -1,2 +1,27 @@
+pub mod assembler;
+pub mod assembly;
+pub mod call_graph;
+pub mod cfg;
 pub mod code_writer;
+pub mod constant_folding;
+pub mod dead_code;
+pub mod debugger;
+pub mod differential;
+pub mod disassembler;
+pub mod emulator;
+pub mod inliner;
+pub mod json_export;
+pub mod jump_threading;
+pub mod label_checker;
+pub mod linter;
 pub mod parser;
+pub mod pass_manager;
+pub mod profiler;
+pub mod program;
+pub mod rom_usage;
+pub mod stack_checker;
+pub mod stack_usage;
+pub mod statics;
+pub mod symbol_checker;
+pub mod trace;
+pub mod vm_interpreter;
@@ -16,6 +16,13 @@ pub trait ParserPublic {
     ///
     /// `true` if there are more commands, `false` otherwise.
     fn has_more_commands(&mut self) -> bool;
+
+    /// Returns a snapshot of the current command.
+    ///
+    /// # Returns
+    ///
+    /// An `Instruction` holding the current command, its type, segment, index and source location.
+    fn instruction(&self) -> Instruction;
 }
 
 /// A private interface for parsing the input file and extracting commands and segments.
@@ -69,6 +76,15 @@ pub struct ParserClass {
 
     /// The index or offset used in VM commands that require it (e.g., push/pop operations).
     pub index: Option<i32>,
+
+    /// The name of the VM file being parsed, recorded on every `Instruction`.
+    pub file_name: String,
+
+    /// The 1-based line number of the current VM command.
+    pub line: usize,
+
+    /// The lint rules allowed on the line of the current VM command.
+    pub allow: Vec<String>,
 }
 
 impl ParserPublic for ParserClass {
@@ -99,6 +115,9 @@ impl ParserPublic for ParserClass {
             command_type: None,
             segment_type: None,
             index: None,
+            file_name: String::new(),
+            line: 0,
+            allow: Vec::new(),
         }
     }
     fn has_more_commands(&mut self) -> bool {
@@ -113,6 +132,9 @@ impl ParserPublic for ParserClass {
                 .unwrap_or(0usize);
 
             if bytes > 0 {
+                // Keep track of the line being read so commands can be located in the source.
+                self.line += 1;
+
                 // Split the line by '/' to remove comments and other unnecessary data.
                 let to_verified: Vec<String> = self
                     .next_instruction
@@ -124,6 +146,9 @@ impl ParserPublic for ParserClass {
                 // Trim the line and take the first part as the verified next instruction.
                 self.next_instruction = to_verified[0].clone().trim().to_string();
 
+                // Record the lint rules allowed by a `// vm-allow(rule)` comment on the line.
+                self.allow = allowed_rules(&to_verified[1..].join("/"));
+
                 // Skip lines that are comments (start with '/').
                 if self.next_instruction.chars().next().unwrap_or('/') == '/' {
                     continue;
@@ -137,6 +162,18 @@ impl ParserPublic for ParserClass {
             };
         }
     }
+
+    fn instruction(&self) -> Instruction {
+        Instruction {
+            file_name: self.file_name.clone(),
+            line: self.line,
+            current_command: self.current_command.clone(),
+            command_type: self.command_type.clone(),
+            segment_type: self.segment_type.clone(),
+            index: self.index,
+            allow: self.allow.clone(),
+        }
+    }
 }
 
 impl ParserPrivate for ParserClass {
@@ -155,6 +192,8 @@ impl ParserPrivate for ParserClass {
         // For push and pop commands, determine the segment type.
         if let Some(Command::PushPop(_)) = self.command_type {
             self.segment_type = self.segment_type();
+        } else {
+            self.segment_type = None;
         }
     }
 
@@ -225,13 +264,34 @@ impl ParserPrivate for ParserClass {
         match a[1].to_lowercase().trim() {
             segment if internal.is_exist(&segment.to_string()) => {
                 // For internal segments, return the corresponding variant of the Segment enum.
-                return Some(Segment::Internal(segment.to_string()));
+                Some(Segment::Internal(segment.to_string()))
             }
             segment if external.is_exist(&segment.to_string()) => {
                 // For external segments, return the corresponding variant of the Segment enum.
-                return Some(Segment::External(segment.to_string()));
+                Some(Segment::External(segment.to_string()))
             }
-            _ => return None, // If the segment type is not recognized, return None.
+            _ => None, // If the segment type is not recognized, return None.
+        }
+    }
+}
+
+/// Returns the lint rules listed in the `vm-allow(rule, ...)` directives of a comment.
+fn allowed_rules(comment: &str) -> Vec<String> {
+    let mut rules: Vec<String> = Vec::new();
+    let mut rest = comment;
+
+    while let Some(start) = rest.find("vm-allow(") {
+        rest = &rest[start + "vm-allow(".len()..];
+        let Some(end) = rest.find(')') else {
+            break;
         };
+        rules.extend(
+            rest[..end]
+                .split(',')
+                .map(|rule| rule.trim().to_string())
+                .filter(|rule| !rule.is_empty()),
+        );
+        rest = &rest[end..];
     }
+    rules
 }
@@ -0,0 +1,296 @@
+use super::assembly::*;
+use super::code_writer::*;
+use super::constant_folding::*;
+use super::dead_code::*;
+use super::inliner::*;
+use super::jump_threading::*;
+use super::program::*;
+use std::fs;
+
+// The level belongs to the code generation options, which choose the `function` template with it.
+pub use super::code_writer::OptLevel;
+
+/// A transformation of the VM instruction stream, run before code generation.
+pub trait VmPass {
+    /// Returns the name of the pass, used to name its dump.
+    fn name(&self) -> String;
+
+    /// Transforms a program.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The output of the previous pass.
+    ///
+    /// # Returns
+    ///
+    /// The transformed program.
+    fn run(&mut self, program: &ProgramClass) -> ProgramClass;
+
+    /// Formats what the last run changed, nothing by default.
+    fn stats(&self) -> String {
+        String::new()
+    }
+}
+
+/// A transformation of the generated assembly, run after code generation.
+pub trait AssemblyPass {
+    /// Returns the name of the pass, used to name its dump.
+    fn name(&self) -> String;
+
+    /// Transforms the generated assembly.
+    ///
+    /// # Arguments
+    ///
+    /// * `assembly` - The output of the code writer or of the previous pass.
+    ///
+    /// # Returns
+    ///
+    /// The transformed assembly, every block still standing for the VM command of its entry.
+    fn run(&mut self, assembly: &AssemblyClass) -> AssemblyClass;
+}
+
+/// A public interface for running the optimisation passes of a level, or any registered pass.
+pub trait PassManagerPublic {
+    /// Creates the pipeline of an optimisation level.
+    ///
+    /// | Level | VM passes | Code generation | Assembly passes |
+    /// | --- | --- | --- | --- |
+    /// | `O0` | none | templates | none |
+    /// | `O1` | constant folding, dead code elimination | specialised, locals looped from 10 | jump threading |
+    /// | `O2` | inlining, constant folding, dead code elimination | specialised, top of stack in D, locals unrolled | jump threading |
+    /// | `Os` | constant folding, dead code elimination | specialised, top of stack in D, locals looped from 4 | jump threading |
+    ///
+    /// # Arguments
+    ///
+    /// * `level` - The optimisation level.
+    fn new(level: OptLevel) -> Self;
+
+    /// Appends a pass to the VM stage of the pipeline.
+    fn add_vm_pass(&mut self, pass: Box<dyn VmPass>);
+
+    /// Appends a pass to the assembly stage of the pipeline.
+    fn add_assembly_pass(&mut self, pass: Box<dyn AssemblyPass>);
+
+    /// Runs the VM passes in order, recording the program after each of them when `dump` is set.
+    ///
+    /// # Returns
+    ///
+    /// The optimised program.
+    fn optimise(&mut self, program: &ProgramClass) -> ProgramClass;
+
+    /// Translates a program with `codegen` and runs the assembly passes in order over the result,
+    /// recording the assembly after the code writer and after each pass when `dump` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to translate, usually the output of `optimise`.
+    /// * `output_file` - The path of the assembly file to write.
+    /// * `init` - Write the bootstrap code first.
+    ///
+    /// # Returns
+    ///
+    /// The code writer, its source map, code and ROM usage describing the final assembly.
+    fn translate(
+        &mut self,
+        program: &ProgramClass,
+        output_file: String,
+        init: bool,
+    ) -> CodeWriterClass;
+}
+
+/// Represents the program between two passes.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dump {
+    /// The pass that produced the program: `input`, `codegen` or the name of the pass.
+    pub pass: String,
+
+    /// `vm` for the VM instruction stream, `asm` for the generated assembly.
+    pub stage: String,
+
+    /// The program, as VM or assembly source.
+    pub code: String,
+}
+
+/// Represents an ordered pipeline of VM passes, code generation modes and assembly passes.
+#[derive(Default)]
+pub struct PassManagerClass {
+    /// The optimisation level the pipeline was created for.
+    pub level: OptLevel,
+
+    /// The passes over the VM instruction stream, in running order.
+    pub vm_passes: Vec<Box<dyn VmPass>>,
+
+    /// The code generation modes of the writer.
+    pub codegen: CodegenOptions,
+
+    /// The passes over the generated assembly, in running order.
+    pub assembly_passes: Vec<Box<dyn AssemblyPass>>,
+
+    /// Record the program between passes in `dumps`.
+    pub dump: bool,
+
+    /// The program before the first pass and after every pass, in running order.
+    pub dumps: Vec<Dump>,
+}
+
+impl PassManagerPublic for PassManagerClass {
+    fn new(level: OptLevel) -> Self {
+        let mut manager = PassManagerClass {
+            level,
+            ..Default::default()
+        };
+        manager.codegen.level = level;
+        // Each pass optimises the program `run` gives it, so it starts without one.
+        if level == OptLevel::O2 {
+            manager.add_vm_pass(Box::new(InlinerClass {
+                threshold: INLINE_THRESHOLD,
+                ..Default::default()
+            }));
+        }
+        if level != OptLevel::O0 {
+            manager.add_vm_pass(Box::<ConstantFoldingClass>::default());
+            manager.add_vm_pass(Box::<DeadCodeClass>::default());
+            manager.codegen.specialise = true;
+            manager.add_assembly_pass(Box::<JumpThreadingClass>::default());
+        }
+        if level == OptLevel::O2 || level == OptLevel::Os {
+            manager.codegen.cache_top = true;
+        }
+        manager
+    }
+
+    fn add_vm_pass(&mut self, pass: Box<dyn VmPass>) {
+        self.vm_passes.push(pass);
+    }
+
+    fn add_assembly_pass(&mut self, pass: Box<dyn AssemblyPass>) {
+        self.assembly_passes.push(pass);
+    }
+
+    fn optimise(&mut self, program: &ProgramClass) -> ProgramClass {
+        let mut program = program.clone();
+        if self.dump {
+            self.dumps.push(vm_dump("input", &program));
+        }
+        for pass in &mut self.vm_passes {
+            program = pass.run(&program);
+            if self.dump {
+                self.dumps.push(vm_dump(&pass.name(), &program));
+            }
+        }
+        program
+    }
+
+    fn translate(
+        &mut self,
+        program: &ProgramClass,
+        output_file: String,
+        init: bool,
+    ) -> CodeWriterClass {
+        let mut writer = CodeWriterClass::new(output_file.clone());
+        writer.options = self.codegen.clone();
+        if init {
+            writer.write_init();
+        }
+        for instruction in &program.instructions {
+            writer.write_instruction(instruction);
+        }
+        writer.flush();
+        if self.assembly_passes.is_empty() && !self.dump {
+            return writer;
+        }
+
+        let mut assembly = AssemblyClass::new(&writer);
+        if self.dump {
+            self.dumps.push(assembly_dump("codegen", &assembly));
+        }
+        for pass in &mut self.assembly_passes {
+            assembly = pass.run(&assembly);
+            if self.dump {
+                self.dumps.push(assembly_dump(&pass.name(), &assembly));
+            }
+        }
+
+        // The writer describes the final assembly, for the ROM usage, the trace and the profiler.
+        writer.source_map = assembly.source_map();
+        writer.rom_usage = assembly.rom_usage();
+        writer.code = assembly
+            .blocks
+            .iter()
+            .map(|block| block.lines.join("\n"))
+            .collect();
+        fs::write(&output_file, assembly.to_asm()).expect("Cannot write output file");
+        writer
+    }
+}
+
+impl VmPass for InlinerClass {
+    fn name(&self) -> String {
+        "inline".to_string()
+    }
+
+    fn run(&mut self, program: &ProgramClass) -> ProgramClass {
+        self.program = program.clone();
+        self.inline()
+    }
+
+    fn stats(&self) -> String {
+        self.report()
+    }
+}
+
+impl VmPass for ConstantFoldingClass {
+    fn name(&self) -> String {
+        "fold-constants".to_string()
+    }
+
+    fn run(&mut self, program: &ProgramClass) -> ProgramClass {
+        self.program = program.clone();
+        self.fold()
+    }
+}
+
+impl VmPass for DeadCodeClass {
+    fn name(&self) -> String {
+        "dead-code".to_string()
+    }
+
+    fn run(&mut self, program: &ProgramClass) -> ProgramClass {
+        self.program = program.clone();
+        self.eliminate()
+    }
+}
+
+impl AssemblyPass for JumpThreadingClass {
+    fn name(&self) -> String {
+        "thread-jumps".to_string()
+    }
+
+    fn run(&mut self, assembly: &AssemblyClass) -> AssemblyClass {
+        self.assembly = assembly.clone();
+        self.thread()
+    }
+}
+
+/// Returns the dump of a VM program, one command per line.
+fn vm_dump(pass: &str, program: &ProgramClass) -> Dump {
+    let mut code = String::new();
+    for instruction in &program.instructions {
+        code.push_str(&instruction.current_command);
+        code.push('\n');
+    }
+    Dump {
+        pass: pass.to_string(),
+        stage: "vm".to_string(),
+        code,
+    }
+}
+
+/// Returns the dump of the generated assembly.
+fn assembly_dump(pass: &str, assembly: &AssemblyClass) -> Dump {
+    Dump {
+        pass: pass.to_string(),
+        stage: "asm".to_string(),
+        code: assembly.to_asm(),
+    }
+}
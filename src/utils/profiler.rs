@@ -0,0 +1,224 @@
+use super::emulator::*;
+use crate::prelude::*;
+use std::collections::BTreeMap;
+
+/// A public interface for measuring where translated programs spend their Hack cycles.
+pub trait ProfilerPublic {
+    /// Creates a new instance of the profiler.
+    ///
+    /// # Arguments
+    ///
+    /// * `emulator` - The emulator loaded with the translated program.
+    /// * `source_map` - The source map recorded by `CodeWriterClass` while translating the program.
+    fn new(emulator: EmulatorClass, source_map: Vec<SourceMapEntry>) -> Self;
+
+    /// Runs the emulator, charging every executed Hack instruction to its VM command and
+    /// to the functions on the call stack.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_cycles` - The maximum number of Hack instructions to execute.
+    ///
+    /// # Returns
+    ///
+    /// The number of profiled cycles.
+    fn run(&mut self, max_cycles: usize) -> usize;
+
+    /// Formats the cycles per function (sorted by self cycles) and per VM opcode as tables.
+    fn report(&self) -> String;
+
+    /// Formats the cycles of every call stack in the folded format read by flamegraph tools,
+    /// one `Sys.init;Main.main;Math.multiply 1234` line per stack.
+    fn folded(&self) -> String;
+}
+
+/// Represents the cycles spent in a VM function.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FunctionProfile {
+    /// The number of times the function was called.
+    pub calls: usize,
+
+    /// The cycles spent in the commands of the function itself.
+    pub self_cycles: usize,
+
+    /// The cycles spent between entering the function and returning from it, callees included.
+    pub inclusive_cycles: usize,
+}
+
+/// Represents the cycles spent in a kind of VM command.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OpcodeProfile {
+    /// The number of executed commands.
+    pub count: usize,
+
+    /// The cycles spent in the commands.
+    pub cycles: usize,
+}
+
+/// Represents a cycle-accurate profiler at the level of VM functions and commands.
+pub struct ProfilerClass {
+    /// The emulator running the translated program.
+    pub emulator: EmulatorClass,
+
+    /// The source map of the translated program.
+    pub source_map: Vec<SourceMapEntry>,
+
+    /// The profile of each function, `TOP_LEVEL` for the code outside of any function.
+    pub functions: BTreeMap<String, FunctionProfile>,
+
+    /// The profile of each VM opcode (`push`, `add`, `call`, ...).
+    pub opcodes: BTreeMap<String, OpcodeProfile>,
+
+    /// The cycles spent in each call stack, its functions joined with `;`.
+    pub stacks: BTreeMap<String, usize>,
+
+    /// The functions being executed, outermost first.
+    pub call_stack: Vec<String>,
+
+    /// The source map entry covering each ROM address.
+    entries: Vec<Option<usize>>,
+
+    /// The source map entry of the previous cycle.
+    previous: Option<usize>,
+}
+
+impl ProfilerPublic for ProfilerClass {
+    fn new(emulator: EmulatorClass, source_map: Vec<SourceMapEntry>) -> Self {
+        let mut entries = vec![None; emulator.rom.len()];
+        for (position, entry) in source_map.iter().enumerate() {
+            for address in entry.address..entry.address + entry.length {
+                if let Some(slot) = entries.get_mut(address) {
+                    *slot = Some(position);
+                }
+            }
+        }
+
+        ProfilerClass {
+            emulator,
+            source_map,
+            functions: BTreeMap::new(),
+            opcodes: BTreeMap::new(),
+            stacks: BTreeMap::new(),
+            call_stack: vec![TOP_LEVEL.to_string()],
+            entries,
+            previous: None,
+        }
+    }
+
+    fn run(&mut self, max_cycles: usize) -> usize {
+        let start = self.emulator.cycles;
+
+        while self.emulator.cycles - start < max_cycles && !self.emulator.is_halted() {
+            let current = self.entries.get(self.emulator.pc).copied().flatten();
+            // A jump back to the start of the same command, as in `label X; if-goto X`, runs it again.
+            let restart = current
+                .is_some_and(|position| self.source_map[position].address == self.emulator.pc);
+            if current != self.previous || restart {
+                self.enter(current);
+            }
+            self.charge(current);
+            self.emulator.step();
+        }
+        self.emulator.cycles - start
+    }
+
+    fn report(&self) -> String {
+        let mut functions: Vec<(&String, &FunctionProfile)> = self.functions.iter().collect();
+        functions.sort_by(|a, b| b.1.self_cycles.cmp(&a.1.self_cycles).then(a.0.cmp(b.0)));
+        let mut opcodes: Vec<(&String, &OpcodeProfile)> = self.opcodes.iter().collect();
+        opcodes.sort_by(|a, b| b.1.cycles.cmp(&a.1.cycles).then(a.0.cmp(b.0)));
+        let total = self.stacks.values().sum::<usize>().max(1);
+
+        let mut output = format!(
+            "{:<32} {:>8} {:>12} {:>7} {:>12} {:>7}\n",
+            "Function", "Calls", "Self", "%", "Inclusive", "%"
+        );
+        for (name, profile) in functions {
+            output.push_str(&format!(
+                "{name:<32} {:>8} {:>12} {:>6.2}% {:>12} {:>6.2}%\n",
+                profile.calls,
+                profile.self_cycles,
+                percent(profile.self_cycles, total),
+                profile.inclusive_cycles,
+                percent(profile.inclusive_cycles, total),
+            ));
+        }
+        output.push_str(&format!(
+            "\n{:<32} {:>8} {:>12} {:>7}\n",
+            "Opcode", "Count", "Cycles", "%"
+        ));
+        for (name, profile) in opcodes {
+            output.push_str(&format!(
+                "{name:<32} {:>8} {:>12} {:>6.2}%\n",
+                profile.count,
+                profile.cycles,
+                percent(profile.cycles, total),
+            ));
+        }
+        output
+    }
+
+    fn folded(&self) -> String {
+        self.stacks
+            .iter()
+            .map(|(stack, cycles)| format!("{stack} {cycles}\n"))
+            .collect()
+    }
+}
+
+impl ProfilerClass {
+    /// Updates the call stack when the execution moves from the previous VM command to `current`.
+    fn enter(&mut self, current: Option<usize>) {
+        let previous = self.previous.map(|position| &self.source_map[position]);
+        let entry = current.map(|position| &self.source_map[position]);
+
+        if let (Some(previous), Some(entry)) = (previous, entry) {
+            // A call (or the bootstrap code) is only left by jumping to the callee, and a tail
+            // call, followed by the `return` it replaces, leaves the function making it.
+            let position = self.previous.unwrap();
+            let tail_call = self
+                .source_map
+                .get(position + 1)
+                .is_some_and(|next| next.command == "return" && next.length == 0);
+            if tail_call && self.call_stack.len() > 1 {
+                self.call_stack.pop();
+            }
+            if previous.command.starts_with("call ") || previous.command == "bootstrap" {
+                self.call_stack.push(entry.function.clone());
+                self.functions
+                    .entry(entry.function.clone())
+                    .or_default()
+                    .calls += 1;
+            } else if previous.command == "return" && self.call_stack.len() > 1 {
+                self.call_stack.pop();
+            }
+        }
+        if let Some(entry) = entry {
+            let opcode = entry.command.split_whitespace().next().unwrap_or_default();
+            self.opcodes.entry(opcode.to_string()).or_default().count += 1;
+        }
+        self.previous = current;
+    }
+
+    /// Charges one cycle to the VM command `current` and to the call stack.
+    fn charge(&mut self, current: Option<usize>) {
+        if let Some(position) = current {
+            let entry = &self.source_map[position];
+            let opcode = entry.command.split_whitespace().next().unwrap_or_default();
+            self.opcodes.entry(opcode.to_string()).or_default().cycles += 1;
+        }
+
+        let top = self.call_stack.last().unwrap().clone();
+        self.functions.entry(top).or_default().self_cycles += 1;
+        // Recursive functions appear several times on the stack but only run once per cycle.
+        for (depth, function) in self.call_stack.iter().enumerate() {
+            if !self.call_stack[..depth].contains(function) {
+                self.functions
+                    .entry(function.clone())
+                    .or_default()
+                    .inclusive_cycles += 1;
+            }
+        }
+        *self.stacks.entry(self.call_stack.join(";")).or_default() += 1;
+    }
+}
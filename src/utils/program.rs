@@ -0,0 +1,114 @@
+use super::parser::*;
+use crate::prelude::*;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+
+/// A public interface for loading a whole VM program into a single instruction stream.
+pub trait ProgramPublic {
+    /// Creates a new program by parsing every VM file found in `paths`.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - Paths of `.vm` files or of directories containing `.vm` files.
+    ///
+    /// # Errors
+    ///
+    /// Errors will occur if a path cannot be opened.
+    fn new(paths: Vec<String>) -> Self;
+
+    /// Splits the instruction stream into the functions it declares.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Function>` in declaration order. Instructions before the first `function`
+    /// command do not belong to any function.
+    fn functions(&self) -> Vec<Function>;
+}
+
+/// Represents a VM program made of the instructions of one or more VM files, in translation order.
+#[derive(Clone, Debug, Default)]
+pub struct ProgramClass {
+    /// The recognised instructions of every file, in the order they were read.
+    pub instructions: Vec<Instruction>,
+}
+
+impl ProgramPublic for ProgramClass {
+    fn new(paths: Vec<String>) -> Self {
+        let mut instructions: Vec<Instruction> = Vec::new();
+
+        for file in paths.iter().flat_map(|path| vm_files(path)) {
+            // Open the VM file and record its name on every instruction read from it.
+            let input = File::open(&file).unwrap_or_else(|_| panic!("Cannot open file {file}"));
+            let mut parser = ParserClass::new(BufReader::new(input));
+            parser.file_name = file_name(&file);
+
+            while parser.has_more_commands() {
+                // Unrecognised commands are skipped, as they are when translating.
+                if parser.command_type.is_some() {
+                    instructions.push(parser.instruction());
+                }
+            }
+        }
+
+        ProgramClass { instructions }
+    }
+
+    fn functions(&self) -> Vec<Function> {
+        let mut functions: Vec<Function> = Vec::new();
+
+        for (position, instruction) in self.instructions.iter().enumerate() {
+            if instruction.name() != "function" {
+                continue;
+            }
+            // The previous function ends where the next one is declared.
+            if let Some(last) = functions.last_mut() {
+                last.end = position;
+            }
+            functions.push(Function {
+                name: instruction.part(1).unwrap_or_default().to_string(),
+                file_name: instruction.file_name.clone(),
+                n_vars: instruction
+                    .part(2)
+                    .and_then(|n| n.parse().ok())
+                    .unwrap_or(0),
+                start: position,
+                end: self.instructions.len(),
+            });
+        }
+        functions
+    }
+}
+
+/// Expands `path` into the list of VM files to translate.
+///
+/// A file is returned as is, a directory is replaced by its `.vm` files sorted by name
+/// so the translation order does not depend on the file system.
+pub fn vm_files(path: &str) -> Vec<String> {
+    let Ok(entries) = fs::read_dir(path) else {
+        return vec![path.to_string()];
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|file| file.is_file() && file.extension().is_some_and(|ext| ext == "vm"))
+        .map(|file| file.to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    files
+}
+
+/// Returns the file name part of `path` (e.g. `Main.vm` for `dir/Main.vm`).
+pub fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
+
+/// Returns the file name of `path` without its extension, as used for static symbols (e.g. `Main`).
+pub fn file_stem(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string())
+}
@@ -0,0 +1,116 @@
+use crate::prelude::*;
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// The number of instructions the Hack ROM holds.
+pub const ROM_SIZE: usize = 32768;
+
+/// A public interface for counting the ROM words taken by the translated program.
+pub trait RomUsagePublic {
+    /// Creates an empty count.
+    fn new() -> Self;
+
+    /// Counts the Hack instructions written for a VM command.
+    ///
+    /// # Arguments
+    ///
+    /// * `entry` - The source map entry of the command.
+    fn add(&mut self, entry: &SourceMapEntry);
+
+    /// Formats the words taken per VM opcode, function and file, largest first, and the total.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The number of words the program may take.
+    fn report(&self, limit: usize) -> String;
+
+    /// Returns an error when the program takes more than `limit` words.
+    ///
+    /// # Arguments
+    ///
+    /// * `limit` - The number of words the program may take.
+    /// * `source_map` - The source map of the program, to locate the first command written past the limit.
+    fn check(&self, limit: usize, source_map: &[SourceMapEntry]) -> Vec<Diagnostic>;
+}
+
+/// Represents the ROM words taken by a program, labels and comments excluded.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RomUsageClass {
+    /// The number of words of the whole program.
+    pub total: usize,
+
+    /// The words of each VM opcode (`push`, `call`, ... and `bootstrap`).
+    pub opcodes: BTreeMap<String, usize>,
+
+    /// The words of each function, `TOP_LEVEL` for the code outside of any function.
+    pub functions: BTreeMap<String, usize>,
+
+    /// The words of each VM file, `bootstrap` for the bootstrap code.
+    pub files: BTreeMap<String, usize>,
+}
+
+impl RomUsagePublic for RomUsageClass {
+    fn new() -> Self {
+        RomUsageClass::default()
+    }
+
+    fn add(&mut self, entry: &SourceMapEntry) {
+        let opcode = entry.command.split_whitespace().next().unwrap_or_default();
+        let function = match entry.function.as_str() {
+            "" => TOP_LEVEL,
+            function => function,
+        };
+        self.total += entry.length;
+        *self.opcodes.entry(opcode.to_string()).or_default() += entry.length;
+        *self.functions.entry(function.to_string()).or_default() += entry.length;
+        *self.files.entry(entry.file_name.clone()).or_default() += entry.length;
+    }
+
+    fn report(&self, limit: usize) -> String {
+        let mut output = String::new();
+        for (title, counts) in [
+            ("Opcode", &self.opcodes),
+            ("Function", &self.functions),
+            ("File", &self.files),
+        ] {
+            let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+            counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+            output.push_str(&format!("{title:<32} {:>8} {:>7}\n", "Words", "%"));
+            for (name, words) in counts {
+                output.push_str(&format!(
+                    "{name:<32} {words:>8} {:>6.2}%\n",
+                    percent(*words, self.total)
+                ));
+            }
+            output.push('\n');
+        }
+        output.push_str(&format!(
+            "ROM usage: {} of {limit} words ({:.2}%)\n",
+            self.total,
+            percent(self.total, limit)
+        ));
+        output
+    }
+
+    fn check(&self, limit: usize, source_map: &[SourceMapEntry]) -> Vec<Diagnostic> {
+        if self.total <= limit {
+            return Vec::new();
+        }
+        let Some(entry) = source_map
+            .iter()
+            .find(|entry| entry.address + entry.length > limit)
+        else {
+            return Vec::new();
+        };
+        vec![Diagnostic {
+            severity: Severity::Error,
+            file_name: entry.file_name.clone(),
+            line: entry.line,
+            message: format!(
+                "the program takes {} ROM words, more than the limit of {limit}, and `{}` is the first command written past it",
+                self.total, entry.command
+            ),
+            rule: None,
+        }]
+    }
+}
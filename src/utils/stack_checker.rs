@@ -0,0 +1,155 @@
+use super::program::*;
+use crate::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// A public interface for checking the height of the working stack of every function.
+pub trait StackCheckerPublic {
+    /// Creates a new instance of the stack checker.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to check.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Computes the stack height before every instruction, following `goto` and `if-goto`
+    /// inside each function (and inside the code before the first function).
+    ///
+    /// The height of a function starts at 0 and calls count as popping their arguments and
+    /// pushing the return value.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Diagnostic>` sorted by location, with an error for every command popping more
+    /// values than the stack holds, every `return` without a value and every instruction
+    /// reached with different heights.
+    fn check(&mut self) -> Vec<Diagnostic>;
+}
+
+/// Represents a static stack-depth analysis of a VM program.
+#[derive(Clone, Debug, Default)]
+pub struct StackCheckerClass {
+    /// The instructions of the program.
+    pub instructions: Vec<Instruction>,
+
+    /// The functions of the program.
+    pub functions: Vec<Function>,
+
+    /// The stack height before each instruction, `None` when the instruction is unreachable.
+    pub depths: Vec<Option<usize>>,
+}
+
+impl StackCheckerPublic for StackCheckerClass {
+    fn new(program: &ProgramClass) -> Self {
+        StackCheckerClass {
+            instructions: program.instructions.clone(),
+            functions: program.functions(),
+            depths: vec![None; program.instructions.len()],
+        }
+    }
+
+    fn check(&mut self) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+        self.depths = vec![None; self.instructions.len()];
+
+        // The code before the first function runs on its own (e.g. test scripts without functions).
+        let first = self
+            .functions
+            .first()
+            .map_or(self.instructions.len(), |function| function.start);
+        let mut regions = vec![(0, first)];
+        regions.extend(
+            self.functions
+                .iter()
+                .map(|function| (function.start, function.end)),
+        );
+
+        for (start, end) in regions {
+            if start < end {
+                self.check_region(start, end, &mut diagnostics);
+            }
+        }
+        diagnostics.sort_by(|a, b| (&a.file_name, a.line).cmp(&(&b.file_name, b.line)));
+        diagnostics
+    }
+}
+
+impl StackCheckerClass {
+    /// Propagates the stack height through the instructions `start..end`, starting at height 0.
+    fn check_region(&mut self, start: usize, end: usize, diagnostics: &mut Vec<Diagnostic>) {
+        let labels: HashMap<&str, usize> = (start..end)
+            .filter(|position| self.instructions[*position].name() == "label")
+            .filter_map(|position| Some((self.instructions[position].part(1)?, position)))
+            .collect();
+        let mut reported: HashSet<usize> = HashSet::new();
+        let mut worklist = vec![start];
+        self.depths[start] = Some(0);
+
+        while let Some(position) = worklist.pop() {
+            let instruction = &self.instructions[position];
+            let depth = self.depths[position].unwrap();
+            let (pops, pushes) = stack_effect(instruction);
+
+            if depth < pops {
+                let message = match instruction.name().as_str() {
+                    "return" => "`return` without a value on the stack".to_string(),
+                    _ => format!(
+                        "`{}` pops {pops} value(s) but the stack holds {depth}",
+                        instruction.current_command
+                    ),
+                };
+                diagnostics.push(Diagnostic::new(Severity::Error, instruction, message));
+            }
+            // Carry on as if the missing values were there, to report each problem once.
+            let after = depth.saturating_sub(pops) + pushes;
+
+            let target = instruction
+                .part(1)
+                .and_then(|label| labels.get(label).copied());
+            let successors: Vec<usize> = match instruction.name().as_str() {
+                "goto" => target.into_iter().collect(),
+                "if-goto" => target.into_iter().chain([position + 1]).collect(),
+                "return" => Vec::new(),
+                _ => vec![position + 1],
+            };
+
+            for successor in successors.into_iter().filter(|successor| *successor < end) {
+                match self.depths[successor] {
+                    None => {
+                        self.depths[successor] = Some(after);
+                        worklist.push(successor);
+                    }
+                    Some(known) if known != after && reported.insert(successor) => {
+                        diagnostics.push(Diagnostic::new(
+                            Severity::Error,
+                            &self.instructions[successor],
+                            format!(
+                                "inconsistent stack height: {known} on one path and {after} on another"
+                            ),
+                        ));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+/// Returns the number of values popped and pushed by `instruction` on the working stack.
+///
+/// A call pops its arguments and pushes the return value.
+pub fn stack_effect(instruction: &Instruction) -> (usize, usize) {
+    let count = || {
+        instruction
+            .part(2)
+            .and_then(|n| n.parse().ok())
+            .unwrap_or(0)
+    };
+    match instruction.name().as_str() {
+        "add" | "sub" | "eq" | "gt" | "lt" | "and" | "or" => (2, 1),
+        "neg" | "not" => (1, 1),
+        "push" => (0, 1),
+        "pop" | "if-goto" | "return" => (1, 0),
+        "call" => (count(), 1),
+        _ => (0, 0),
+    }
+}
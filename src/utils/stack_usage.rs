@@ -0,0 +1,271 @@
+use super::call_graph::*;
+use super::program::*;
+use super::stack_checker::*;
+use super::statics::*;
+use crate::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The RAM address the heap starts at, one past the stack region.
+pub const HEAP_BASE: usize = 2048;
+
+/// The number of words a call pushes to save the caller: return address, `LCL`, `ARG`, `THIS` and `THAT`.
+pub const CALL_FRAME: usize = 5;
+
+/// A public interface for bounding the stack used by a program.
+pub trait StackUsagePublic {
+    /// Computes the worst-case stack usage of every function.
+    ///
+    /// The usage of a function is its local variables plus the highest point of its working
+    /// stack, where a call adds the saved caller frame and the usage of the called function.
+    /// Functions that are recursive, or that call a recursive function, have no bound.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to analyse.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Returns the words used above `SP` when `entry` is called by the bootstrap code,
+    /// `None` when it is unbounded or not declared.
+    fn bound(&self, entry: &str) -> Option<usize>;
+
+    /// Formats the usage of every function, the recursive cycles and the bound of `entry`.
+    fn report(&self, entry: &str) -> String;
+
+    /// Returns a warning when the bound of `entry` does not fit between the stack base (256)
+    /// and the heap (2048).
+    fn check(&self, entry: &str) -> Vec<Diagnostic>;
+}
+
+/// Represents the worst-case stack usage of the functions of a program.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StackUsageClass {
+    /// The declared functions, in declaration order.
+    pub functions: Vec<Function>,
+
+    /// The words used by each function without its calls: local variables and working stack.
+    pub frames: BTreeMap<String, usize>,
+
+    /// The words used by each function and its calls, `None` when unbounded.
+    pub usage: BTreeMap<String, Option<usize>>,
+
+    /// The sets of functions calling each other recursively, each sorted by name.
+    pub cycles: Vec<Vec<String>>,
+
+    /// The `function` instruction of each function.
+    #[serde(skip)]
+    declarations: BTreeMap<String, Instruction>,
+}
+
+impl StackUsagePublic for StackUsageClass {
+    fn new(program: &ProgramClass) -> Self {
+        let graph = CallGraphClass::new(program);
+        let mut checker = StackCheckerClass::new(program);
+        checker.check();
+
+        let cycles = recursive_cycles(&graph.calls);
+        let recursive: BTreeSet<&String> = cycles.iter().flatten().collect();
+
+        // The highest point of each function, and the height of the stack at each call.
+        let mut frames: BTreeMap<String, usize> = BTreeMap::new();
+        let mut calls: BTreeMap<String, Vec<(usize, String)>> = BTreeMap::new();
+        for function in &graph.functions {
+            let mut frame = 0;
+            let function_calls = calls.entry(function.name.clone()).or_default();
+            for position in function.start..function.end {
+                let Some(depth) = checker.depths[position] else {
+                    continue;
+                };
+                let instruction = &program.instructions[position];
+                let (pops, pushes) = stack_effect(instruction);
+                frame = frame.max(depth).max(depth.saturating_sub(pops) + pushes);
+                if instruction.name() == "call" {
+                    let callee = instruction.part(1).unwrap_or_default().to_string();
+                    function_calls.push((function.n_vars + depth, callee));
+                }
+            }
+            frames.insert(function.name.clone(), function.n_vars + frame);
+        }
+
+        // Functions are visited callees first, which always ends as recursion has no bound.
+        let mut usage: BTreeMap<String, Option<usize>> = BTreeMap::new();
+        for function in &graph.functions {
+            compute_usage(&function.name, &frames, &calls, &recursive, &mut usage);
+        }
+
+        StackUsageClass {
+            functions: graph.functions.clone(),
+            frames,
+            usage,
+            cycles,
+            declarations: graph
+                .functions
+                .iter()
+                .map(|function| {
+                    (
+                        function.name.clone(),
+                        program.instructions[function.start].clone(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn bound(&self, entry: &str) -> Option<usize> {
+        self.usage
+            .get(entry)
+            .copied()
+            .flatten()
+            .map(|usage| CALL_FRAME + usage)
+    }
+
+    fn report(&self, entry: &str) -> String {
+        let mut output = format!("{:<32} {:>8} {:>12}\n", "Function", "Frame", "Worst case");
+        for function in &self.functions {
+            let usage = match self.usage[&function.name] {
+                Some(usage) => usage.to_string(),
+                None => "unbounded".to_string(),
+            };
+            output.push_str(&format!(
+                "{:<32} {:>8} {:>12}\n",
+                function.name, self.frames[&function.name], usage
+            ));
+        }
+
+        if !self.cycles.is_empty() {
+            output.push_str("\nRecursive cycles (functions calling each other):\n");
+            for cycle in &self.cycles {
+                output.push_str(&format!("  {}\n", cycle.join(", ")));
+            }
+        }
+
+        let region = HEAP_BASE - STACK_BASE;
+        match (self.bound(entry), self.usage.get(entry)) {
+            (Some(bound), _) => output.push_str(&format!(
+                "\nWorst-case stack usage from {entry}: {bound} of {region} words (RAM {STACK_BASE}-{})\n",
+                STACK_BASE + bound - 1
+            )),
+            (None, Some(_)) => output.push_str(&format!(
+                "\nWorst-case stack usage from {entry}: unbounded (recursive)\n"
+            )),
+            (None, None) => {}
+        }
+        output
+    }
+
+    fn check(&self, entry: &str) -> Vec<Diagnostic> {
+        let region = HEAP_BASE - STACK_BASE;
+        match (self.bound(entry), self.declarations.get(entry)) {
+            (Some(bound), Some(declaration)) if bound > region => vec![Diagnostic::new(
+                Severity::Warning,
+                declaration,
+                format!(
+                    "the stack can grow to {bound} words from {entry}, more than the {region} words between RAM {STACK_BASE} and the heap at {HEAP_BASE}"
+                ),
+            )],
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Computes the usage of `function` after the usage of the functions it calls.
+fn compute_usage(
+    function: &str,
+    frames: &BTreeMap<String, usize>,
+    calls: &BTreeMap<String, Vec<(usize, String)>>,
+    recursive: &BTreeSet<&String>,
+    usage: &mut BTreeMap<String, Option<usize>>,
+) -> Option<usize> {
+    if let Some(known) = usage.get(function) {
+        return *known;
+    }
+    // Calls to undeclared functions are reported by the symbol checks and use no stack here.
+    let Some(frame) = frames.get(function) else {
+        return Some(0);
+    };
+    let mut result = match recursive.iter().any(|name| *name == function) {
+        true => None,
+        false => Some(*frame),
+    };
+    if result.is_some() {
+        for (height, callee) in &calls[function] {
+            let callee_usage = compute_usage(callee, frames, calls, recursive, usage);
+            result = match (result, callee_usage) {
+                (Some(current), Some(callee_usage)) => {
+                    Some(current.max(height + CALL_FRAME + callee_usage))
+                }
+                _ => None,
+            };
+        }
+    }
+    usage.insert(function.to_string(), result);
+    result
+}
+
+/// Returns the groups of functions calling each other, directly or not, using Tarjan's algorithm.
+fn recursive_cycles(calls: &BTreeMap<String, BTreeSet<String>>) -> Vec<Vec<String>> {
+    struct Tarjan<'a> {
+        calls: &'a BTreeMap<String, BTreeSet<String>>,
+        index: BTreeMap<&'a str, usize>,
+        low: BTreeMap<&'a str, usize>,
+        stack: Vec<&'a str>,
+        on_stack: BTreeSet<&'a str>,
+        cycles: Vec<Vec<String>>,
+    }
+
+    impl<'a> Tarjan<'a> {
+        fn visit(&mut self, function: &'a str) {
+            let index = self.index.len();
+            self.index.insert(function, index);
+            self.low.insert(function, index);
+            self.stack.push(function);
+            self.on_stack.insert(function);
+
+            for callee in self.calls.get(function).into_iter().flatten() {
+                if !self.calls.contains_key(callee) {
+                    continue;
+                }
+                if !self.index.contains_key(callee.as_str()) {
+                    self.visit(callee);
+                    let low = self.low[function].min(self.low[callee.as_str()]);
+                    self.low.insert(function, low);
+                } else if self.on_stack.contains(callee.as_str()) {
+                    let low = self.low[function].min(self.index[callee.as_str()]);
+                    self.low.insert(function, low);
+                }
+            }
+
+            if self.low[function] == self.index[function] {
+                let mut component: Vec<String> = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(member);
+                    component.push(member.to_string());
+                    if member == function {
+                        break;
+                    }
+                }
+                let calls_itself = self.calls[function].contains(function);
+                if component.len() > 1 || calls_itself {
+                    component.sort();
+                    self.cycles.push(component);
+                }
+            }
+        }
+    }
+
+    let mut tarjan = Tarjan {
+        calls,
+        index: BTreeMap::new(),
+        low: BTreeMap::new(),
+        stack: Vec::new(),
+        on_stack: BTreeSet::new(),
+        cycles: Vec::new(),
+    };
+    for function in calls.keys() {
+        if !tarjan.index.contains_key(function.as_str()) {
+            tarjan.visit(function);
+        }
+    }
+    tarjan.cycles.sort();
+    tarjan.cycles
+}
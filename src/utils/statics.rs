@@ -0,0 +1,200 @@
+use super::code_writer::scoped_label;
+use super::program::*;
+use crate::prelude::*;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// The first RAM address the assembler allocates variables at.
+pub const STATIC_BASE: usize = 16;
+
+/// The RAM address the stack starts at, one past the static region.
+pub const STACK_BASE: usize = 256;
+
+/// A public interface for predicting where the static variables of a program are allocated.
+pub trait StaticsPublic {
+    /// Allocates the static variables of a program the way the assembler will.
+    ///
+    /// `push/pop static i` in `File.vm` is written as the symbol `File.i`, and the assembler
+    /// allocates every new symbol at the next RAM address from 16, in order of first use.
+    /// The function a `call` names and the label a `goto` or `if-goto` names are allocated too
+    /// when the program does not declare them. The bootstrap code adds none, as it is only
+    /// written for programs declaring `Sys.init`.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to translate.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Formats the address of every static variable and the number of statics of every module.
+    fn report(&self) -> String;
+
+    /// Returns an error at the first static variable allocated past the static region (RAM 16-255),
+    /// where it would overwrite the stack.
+    fn check(&self) -> Vec<Diagnostic>;
+}
+
+/// Represents a static variable and the place it is first used.
+#[derive(Clone, Debug, Serialize)]
+pub struct Static {
+    /// The symbol of the variable (e.g. `Main.3`).
+    pub symbol: String,
+    /// The module (VM file name without extension) declaring the variable, empty for an
+    /// undeclared function or label.
+    pub module: String,
+    /// The RAM address of the variable.
+    pub address: usize,
+    /// The first instruction using the variable.
+    pub instruction: Instruction,
+}
+
+/// Represents the allocation of the static variables of a program.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StaticsClass {
+    /// The static variables, in allocation order.
+    pub statics: Vec<Static>,
+
+    /// The number of static variables of each module.
+    pub modules: BTreeMap<String, usize>,
+
+    /// The undeclared functions and labels allocated as variables, in allocation order.
+    pub undeclared: Vec<Static>,
+}
+
+impl StaticsPublic for StaticsClass {
+    fn new(program: &ProgramClass) -> Self {
+        let mut allocation = StaticsClass::default();
+        let mut symbols: HashSet<String> = HashSet::new();
+
+        // The functions and labels are assembler labels, wherever they are declared.
+        let functions: HashSet<String> = program
+            .functions()
+            .into_iter()
+            .map(|function| function.name)
+            .collect();
+        let mut labels: HashSet<String> = HashSet::new();
+        let mut scope = String::new();
+        for instruction in &program.instructions {
+            let name = instruction.part(1).unwrap_or_default();
+            match instruction.name().as_str() {
+                "function" => scope = name.to_string(),
+                "label" => {
+                    labels.insert(scoped_label(&scope, name));
+                }
+                _ => {}
+            }
+        }
+
+        scope.clear();
+        for instruction in &program.instructions {
+            let name = instruction.part(1).unwrap_or_default();
+            match instruction.name().as_str() {
+                "function" => scope = name.to_string(),
+                "call" if !functions.contains(name) => {
+                    allocation.allocate(&mut symbols, name.to_string(), instruction, false);
+                }
+                "goto" | "if-goto" if !labels.contains(&scoped_label(&scope, name)) => {
+                    let symbol = scoped_label(&scope, name);
+                    allocation.allocate(&mut symbols, symbol, instruction, false);
+                }
+                "push" | "pop" if name.to_lowercase() == "static" => {
+                    let module = file_stem(&instruction.file_name);
+                    let symbol = format!("{module}.{}", instruction.index.unwrap_or(0));
+                    allocation.allocate(&mut symbols, symbol, instruction, true);
+                }
+                _ => {}
+            }
+        }
+        allocation
+    }
+
+    fn report(&self) -> String {
+        let mut output = format!(
+            "Static allocation (RAM {STATIC_BASE}-{}):\n",
+            STACK_BASE - 1
+        );
+        let mut variables: Vec<&Static> = self.statics.iter().chain(&self.undeclared).collect();
+        variables.sort_by_key(|variable| variable.address);
+        for variable in variables {
+            let note = match variable.module.is_empty() {
+                true => " (undeclared)",
+                false => "",
+            };
+            output.push_str(&format!(
+                "{:>6}  {}{note}\n",
+                variable.address, variable.symbol
+            ));
+        }
+        output.push_str(&format!("\n{:<32} {:>8}\n", "Module", "Statics"));
+        for (module, count) in &self.modules {
+            output.push_str(&format!("{module:<32} {count:>8}\n"));
+        }
+        if !self.undeclared.is_empty() {
+            output.push_str(&format!(
+                "{:<32} {:>8}\n",
+                "(undeclared)",
+                self.undeclared.len()
+            ));
+        }
+        output.push_str(&format!(
+            "{:<32} {:>8} of {}\n",
+            "Total",
+            self.statics.len() + self.undeclared.len(),
+            STACK_BASE - STATIC_BASE
+        ));
+        output
+    }
+
+    fn check(&self) -> Vec<Diagnostic> {
+        self.statics
+            .iter()
+            .find(|variable| variable.address >= STACK_BASE)
+            .map(|variable| {
+                Diagnostic::new(
+                    Severity::Error,
+                    &variable.instruction,
+                    format!(
+                        "static {} is allocated at RAM {}, past the {} addresses of the static region ({} statics in total)",
+                        variable.symbol,
+                        variable.address,
+                        STACK_BASE - STATIC_BASE,
+                        self.statics.len()
+                    ),
+                )
+            })
+            .into_iter()
+            .collect()
+    }
+}
+
+impl StaticsClass {
+    /// Allocates `symbol` at the next free address unless it already has one.
+    fn allocate(
+        &mut self,
+        symbols: &mut HashSet<String>,
+        symbol: String,
+        instruction: &Instruction,
+        is_static: bool,
+    ) {
+        if !symbols.insert(symbol.clone()) {
+            return;
+        }
+        let address = STATIC_BASE + symbols.len() - 1;
+        if !is_static {
+            self.undeclared.push(Static {
+                symbol,
+                module: String::new(),
+                address,
+                instruction: instruction.clone(),
+            });
+            return;
+        }
+        let module = file_stem(&instruction.file_name);
+        *self.modules.entry(module.clone()).or_default() += 1;
+        self.statics.push(Static {
+            symbol,
+            module,
+            address,
+            instruction: instruction.clone(),
+        });
+    }
+}
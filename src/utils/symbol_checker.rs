@@ -0,0 +1,112 @@
+use super::call_graph::*;
+use super::program::*;
+use crate::prelude::*;
+use std::collections::HashMap;
+
+/// A public interface for checking the functions declared and called across the files of a program.
+pub trait SymbolCheckerPublic {
+    /// Creates a new instance of the symbol checker.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to check.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Checks the `function` and `call` commands of the whole program.
+    ///
+    /// # Arguments
+    ///
+    /// * `bootstrap` - `true` when the program is translated with the bootstrap code, which calls `Sys.init`.
+    ///
+    /// # Returns
+    ///
+    /// A `Vec<Diagnostic>` with an error for every call to an undeclared function, every function
+    /// declared more than once and a missing `Sys.init` when bootstrapping, and a warning for every
+    /// call whose number of arguments differs from the first call to the same function.
+    fn check(&self, bootstrap: bool) -> Vec<Diagnostic>;
+}
+
+/// Represents a whole-program check of the function symbols.
+#[derive(Clone, Debug, Default)]
+pub struct SymbolCheckerClass {
+    /// The instructions of the program.
+    pub instructions: Vec<Instruction>,
+}
+
+impl SymbolCheckerPublic for SymbolCheckerClass {
+    fn new(program: &ProgramClass) -> Self {
+        SymbolCheckerClass {
+            instructions: program.instructions.clone(),
+        }
+    }
+
+    fn check(&self, bootstrap: bool) -> Vec<Diagnostic> {
+        let mut diagnostics: Vec<Diagnostic> = Vec::new();
+
+        // The first declaration of every function.
+        let mut declarations: HashMap<&str, &Instruction> = HashMap::new();
+        for instruction in self.commands("function") {
+            let name = instruction.part(1).unwrap_or_default();
+            match declarations.get(name) {
+                Some(first) => diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    instruction,
+                    format!(
+                        "function {name} is already declared at {}:{}",
+                        first.file_name, first.line
+                    ),
+                )),
+                None => {
+                    declarations.insert(name, instruction);
+                }
+            }
+        }
+
+        // The first call to every function, whose argument count the other calls must match.
+        let mut calls: HashMap<&str, &Instruction> = HashMap::new();
+        for instruction in self.commands("call") {
+            let name = instruction.part(1).unwrap_or_default();
+            if !declarations.contains_key(name) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Error,
+                    instruction,
+                    format!("call to undeclared function {name}"),
+                ));
+            }
+            let first = *calls.entry(name).or_insert(instruction);
+            if first.part(2) != instruction.part(2) {
+                diagnostics.push(Diagnostic::new(
+                    Severity::Warning,
+                    instruction,
+                    format!(
+                        "{name} is called with {} argument(s) here but with {} at {}:{}",
+                        instruction.part(2).unwrap_or("no"),
+                        first.part(2).unwrap_or("no"),
+                        first.file_name,
+                        first.line
+                    ),
+                ));
+            }
+        }
+
+        if bootstrap && !declarations.contains_key(ENTRY_POINT) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                file_name: "bootstrap".to_string(),
+                line: 0,
+                message: format!("the bootstrap code calls {ENTRY_POINT}, which is not declared"),
+                rule: None,
+            });
+        }
+        diagnostics
+    }
+}
+
+impl SymbolCheckerClass {
+    /// Returns the instructions of the program named `name`.
+    fn commands<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Instruction> {
+        self.instructions
+            .iter()
+            .filter(move |instruction| instruction.name() == name)
+    }
+}
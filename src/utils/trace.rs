@@ -0,0 +1,98 @@
+use super::emulator::*;
+use crate::prelude::*;
+use std::collections::HashMap;
+use std::io::Write;
+
+/// A public interface for running translated programs while reporting the VM commands being executed.
+pub trait TracerPublic {
+    /// Creates a new instance of the tracer.
+    ///
+    /// # Arguments
+    ///
+    /// * `emulator` - The emulator loaded with the translated program.
+    /// * `source_map` - The source map recorded by `CodeWriterClass` while translating the program.
+    fn new(emulator: EmulatorClass, source_map: Vec<SourceMapEntry>) -> Self;
+
+    /// Runs the emulator, writing one line to `output` each time the first Hack instruction
+    /// of a VM command is about to be executed.
+    ///
+    /// Each line holds the source location, the command, the stack pointer and the value on
+    /// top of the stack, e.g. `Main.vm:12  push local 0  SP=261 top=3`.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_cycles` - The maximum number of Hack instructions to execute.
+    /// * `output` - Where the trace is written.
+    ///
+    /// # Returns
+    ///
+    /// The number of traced VM commands.
+    fn run(&mut self, max_cycles: usize, output: &mut dyn Write) -> usize;
+}
+
+/// Represents an emulator trace at the level of VM commands.
+pub struct TracerClass {
+    /// The emulator running the translated program.
+    pub emulator: EmulatorClass,
+
+    /// The source map of the translated program.
+    pub source_map: Vec<SourceMapEntry>,
+
+    /// The functions to trace. Every function is traced when empty.
+    pub functions: Vec<String>,
+
+    /// The source map entry starting at each ROM address.
+    entries: HashMap<usize, usize>,
+}
+
+impl TracerPublic for TracerClass {
+    fn new(emulator: EmulatorClass, source_map: Vec<SourceMapEntry>) -> Self {
+        // Commands without instructions (labels) cannot be executed and are not traced.
+        let entries = source_map
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| entry.length > 0)
+            .map(|(position, entry)| (entry.address, position))
+            .collect();
+
+        TracerClass {
+            emulator,
+            source_map,
+            functions: Vec::new(),
+            entries,
+        }
+    }
+
+    fn run(&mut self, max_cycles: usize, output: &mut dyn Write) -> usize {
+        let mut traced = 0;
+        let start = self.emulator.cycles;
+
+        while self.emulator.cycles - start < max_cycles && !self.emulator.is_halted() {
+            if let Some(position) = self.entries.get(&self.emulator.pc) {
+                let entry = &self.source_map[*position];
+                if self.functions.is_empty() || self.functions.contains(&entry.function) {
+                    // Stop tracing when the output is closed (e.g. piped into `head`).
+                    if writeln!(output, "{}", self.describe(entry)).is_err() {
+                        break;
+                    }
+                    traced += 1;
+                }
+            }
+            self.emulator.step();
+        }
+        traced
+    }
+}
+
+impl TracerClass {
+    /// Formats the trace line of `entry` from the current state of the emulator.
+    fn describe(&self, entry: &SourceMapEntry) -> String {
+        let sp = self.emulator.ram[0];
+        let top = match (sp as u16 as usize).checked_sub(1) {
+            Some(address) if address < RAM_SIZE => self.emulator.ram[address].to_string(),
+            _ => "-".to_string(),
+        };
+        let location = format!("{}:{}", entry.file_name, entry.line);
+        format!("{location:<20} {:<32} SP={sp} top={top}", entry.command)
+    }
+}
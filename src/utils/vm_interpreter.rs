@@ -0,0 +1,411 @@
+use super::code_writer::scoped_label;
+use super::program::*;
+use crate::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// A public interface for executing VM programs directly, without translating them.
+pub trait InterpreterPublic {
+    /// Creates a new instance of the interpreter ready to execute `program`.
+    ///
+    /// When the program declares `Sys.init` the execution starts with a call to it, as the
+    /// bootstrap code does. Otherwise it starts at the first instruction.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - The program to execute.
+    fn new(program: &ProgramClass) -> Self;
+
+    /// Executes the instruction at `pc`.
+    ///
+    /// # Returns
+    ///
+    /// The `Step` describing what the instruction did, or `None` if the interpreter is halted.
+    fn step(&mut self) -> Option<Step>;
+
+    /// Executes instructions until the program halts or `max_steps` instructions have been executed.
+    ///
+    /// # Returns
+    ///
+    /// The number of executed instructions.
+    fn run(&mut self, max_steps: usize) -> usize;
+}
+
+/// Describes the effect of one executed instruction on the call stack.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    /// The instruction did not enter nor leave a function.
+    Next,
+    /// The instruction called the named function.
+    Call(String),
+    /// The instruction returned from the named function to its caller.
+    Return(String),
+}
+
+/// Represents the frame of a function being executed.
+#[derive(Clone, Debug, Default)]
+pub struct Frame {
+    /// The name of the function, empty for the code that runs outside of any function.
+    pub function: String,
+
+    /// The index of the instruction to resume at when the function returns.
+    pub return_address: usize,
+
+    /// The `argument` segment.
+    pub argument: Vec<i16>,
+
+    /// The `local` segment.
+    pub local: Vec<i16>,
+
+    /// The working stack of the function.
+    pub stack: Vec<i16>,
+
+    /// The `pointer` segment of the caller, restored when the function returns.
+    pub saved_pointer: [i16; 2],
+}
+
+/// Represents a reference VM interpreter that executes the VM semantics on an abstract machine.
+///
+/// Functions get their own frame with separate `argument`, `local` and working stack, so the
+/// results do not depend on how `CodeWriterClass` lays frames out in RAM.
+pub struct InterpreterClass {
+    /// The instructions being executed.
+    pub instructions: Vec<Instruction>,
+
+    /// The index of the next instruction to execute.
+    pub pc: usize,
+
+    /// The call stack, the last frame being the one executing.
+    pub frames: Vec<Frame>,
+
+    /// The `pointer` segment: the base addresses of `this` and `that`.
+    pub pointer: [i16; 2],
+
+    /// The `temp` segment.
+    pub temp: [i16; 8],
+
+    /// The `static` segment of every file, keyed by the assembly symbol `<File>.<index>`.
+    pub statics: BTreeMap<String, i16>,
+
+    /// The memory reached through `this` and `that`, keyed by address.
+    pub heap: BTreeMap<usize, i16>,
+
+    /// The number of instructions executed so far.
+    pub steps: usize,
+
+    /// `true` once the program has finished or failed.
+    pub halted: bool,
+
+    /// The reason the program failed, if it did.
+    pub error: Option<String>,
+
+    /// The index of every label, keyed by its assembly symbol (see `scoped_label`).
+    labels: HashMap<String, usize>,
+
+    /// The name of the function every instruction belongs to, empty before the first function.
+    scopes: Vec<String>,
+
+    /// The index of every `function` instruction, keyed by function name.
+    functions: HashMap<String, usize>,
+}
+
+impl InterpreterPublic for InterpreterClass {
+    fn new(program: &ProgramClass) -> Self {
+        let instructions = program.instructions.clone();
+
+        let mut labels: HashMap<String, usize> = HashMap::new();
+        let mut functions: HashMap<String, usize> = HashMap::new();
+        let mut scopes: Vec<String> = Vec::new();
+        let mut scope = String::new();
+        for (position, instruction) in instructions.iter().enumerate() {
+            let name = instruction.part(1).unwrap_or_default().to_string();
+            match instruction.name().as_str() {
+                "label" => {
+                    labels
+                        .entry(scoped_label(&scope, &name))
+                        .or_insert(position);
+                }
+                "function" => {
+                    functions.entry(name.clone()).or_insert(position);
+                    scope = name;
+                }
+                _ => {}
+            };
+            scopes.push(scope.clone());
+        }
+
+        let mut interpreter = InterpreterClass {
+            halted: instructions.is_empty(),
+            instructions,
+            pc: 0,
+            frames: vec![Frame::default()],
+            pointer: [0; 2],
+            temp: [0; 8],
+            statics: BTreeMap::new(),
+            heap: BTreeMap::new(),
+            steps: 0,
+            error: None,
+            labels,
+            scopes,
+            functions,
+        };
+
+        // Start like the bootstrap code when the program has an entry point.
+        if interpreter.functions.contains_key("Sys.init") {
+            interpreter.call("Sys.init", 0, usize::MAX).unwrap();
+        }
+        interpreter
+    }
+
+    fn step(&mut self) -> Option<Step> {
+        if self.halted {
+            return None;
+        }
+        let Some(instruction) = self.instructions.get(self.pc).cloned() else {
+            self.halted = true;
+            return None;
+        };
+        self.steps += 1;
+        self.pc += 1;
+
+        match self.execute(&instruction) {
+            Ok(step) => Some(step),
+            Err(message) => {
+                self.error = Some(format!(
+                    "{}:{}: {message} ({})",
+                    instruction.file_name, instruction.line, instruction.current_command
+                ));
+                self.halted = true;
+                None
+            }
+        }
+    }
+
+    fn run(&mut self, max_steps: usize) -> usize {
+        let start = self.steps;
+        while self.steps - start < max_steps && self.step().is_some() {}
+        self.steps - start
+    }
+}
+
+impl InterpreterClass {
+    /// Returns the frame of the function being executed.
+    pub fn frame(&self) -> &Frame {
+        self.frames.last().unwrap()
+    }
+
+    /// Returns the name of the VM file of the instruction at `pc`, without its extension.
+    fn module(&self) -> String {
+        let position = self.pc.saturating_sub(1);
+        file_stem(&self.instructions[position].file_name)
+    }
+
+    /// Executes a single instruction, `pc` already pointing to the next one.
+    fn execute(&mut self, instruction: &Instruction) -> Result<Step, String> {
+        let command = instruction.name();
+        match &instruction.command_type {
+            Some(Command::Arithmetic(_)) => {
+                let y = self.pop()?;
+                let value = match command.as_str() {
+                    "neg" => y.wrapping_neg(),
+                    "not" => !y,
+                    _ => arithmetic(&command, self.pop()?, y),
+                };
+                self.push(value);
+                Ok(Step::Next)
+            }
+            Some(Command::PushPop(_)) => {
+                let segment = instruction.part(1).unwrap_or_default().to_lowercase();
+                let index = instruction.index.unwrap_or(0);
+                if command == "push" {
+                    let value = self.read(&segment, index)?;
+                    self.push(value);
+                } else {
+                    let value = self.pop()?;
+                    self.write(&segment, index, value)?;
+                }
+                Ok(Step::Next)
+            }
+            Some(Command::Branch(_)) => {
+                let label = instruction.part(1).unwrap_or_default();
+                match command.as_str() {
+                    "goto" => self.jump(label)?,
+                    "if-goto" if self.pop()? != 0 => self.jump(label)?,
+                    _ => {}
+                }
+                Ok(Step::Next)
+            }
+            Some(Command::Function(_)) => match command.as_str() {
+                "function" => {
+                    let n_vars = parse_count(instruction.part(2))?;
+                    self.frames.last_mut().unwrap().local = vec![0; n_vars];
+                    Ok(Step::Next)
+                }
+                "call" => {
+                    let function = instruction.part(1).unwrap_or_default();
+                    let n_args = parse_count(instruction.part(2))?;
+                    self.call(function, n_args, self.pc)?;
+                    Ok(Step::Call(function.to_string()))
+                }
+                _ => {
+                    let value = self.pop()?;
+                    let frame = self.frames.pop().unwrap();
+                    if frame.return_address == usize::MAX || self.frames.is_empty() {
+                        // Returning from the entry point ends the program.
+                        self.frames.push(frame.clone());
+                        self.halted = true;
+                    } else {
+                        self.push(value);
+                        self.pointer = frame.saved_pointer;
+                        self.pc = frame.return_address;
+                    }
+                    Ok(Step::Return(frame.function))
+                }
+            },
+            None => Ok(Step::Next),
+        }
+    }
+
+    /// Enters `function`, moving the top `n_args` values of the stack into its `argument` segment.
+    fn call(&mut self, function: &str, n_args: usize, return_address: usize) -> Result<(), String> {
+        let Some(start) = self.functions.get(function).copied() else {
+            return Err(format!("function {function} is not declared"));
+        };
+        let stack = &mut self.frames.last_mut().unwrap().stack;
+        if stack.len() < n_args {
+            return Err("stack underflow".to_string());
+        }
+        let argument = stack.split_off(stack.len() - n_args);
+        self.frames.push(Frame {
+            function: function.to_string(),
+            return_address,
+            argument,
+            local: Vec::new(),
+            stack: Vec::new(),
+            saved_pointer: self.pointer,
+        });
+        self.pc = start;
+        Ok(())
+    }
+
+    /// Continues the execution at `label`.
+    fn jump(&mut self, label: &str) -> Result<(), String> {
+        let symbol = scoped_label(&self.scopes[self.pc - 1], label);
+        let Some(target) = self.labels.get(&symbol).copied() else {
+            return Err(format!("label {label} is not declared"));
+        };
+        // A jump to the label right before it is the usual `label END; goto END` end of program.
+        if target + 2 == self.pc {
+            self.halted = true;
+        }
+        self.pc = target;
+        Ok(())
+    }
+
+    fn push(&mut self, value: i16) {
+        self.frames.last_mut().unwrap().stack.push(value);
+    }
+
+    fn pop(&mut self) -> Result<i16, String> {
+        let frame = self.frames.last_mut().unwrap();
+        frame
+            .stack
+            .pop()
+            .ok_or_else(|| "stack underflow".to_string())
+    }
+
+    /// Reads `segment[index]`.
+    fn read(&mut self, segment: &str, index: i32) -> Result<i16, String> {
+        let position = usize::try_from(index).map_err(|_| "negative index".to_string())?;
+        let frame = self.frames.last_mut().unwrap();
+        Ok(match segment {
+            "constant" => index as i16,
+            "local" => *slot(&mut frame.local, position),
+            "argument" => *slot(&mut frame.argument, position),
+            "static" => {
+                let key = format!("{}.{index}", self.module());
+                self.statics.get(&key).copied().unwrap_or(0)
+            }
+            "temp" => *self.temp.get(position).ok_or("temp index out of range")?,
+            "pointer" => *self
+                .pointer
+                .get(position)
+                .ok_or("pointer index out of range")?,
+            "this" | "that" => {
+                let address = self.heap_address(segment, position);
+                self.heap.get(&address).copied().unwrap_or(0)
+            }
+            _ => return Err(format!("unknown segment {segment}")),
+        })
+    }
+
+    /// Writes `value` into `segment[index]`.
+    fn write(&mut self, segment: &str, index: i32, value: i16) -> Result<(), String> {
+        let position = usize::try_from(index).map_err(|_| "negative index".to_string())?;
+        let frame = self.frames.last_mut().unwrap();
+        match segment {
+            "local" => *slot(&mut frame.local, position) = value,
+            "argument" => *slot(&mut frame.argument, position) = value,
+            "static" => {
+                let key = format!("{}.{index}", self.module());
+                self.statics.insert(key, value);
+            }
+            "temp" => {
+                *self
+                    .temp
+                    .get_mut(position)
+                    .ok_or("temp index out of range")? = value
+            }
+            "pointer" => {
+                *self
+                    .pointer
+                    .get_mut(position)
+                    .ok_or("pointer index out of range")? = value
+            }
+            "this" | "that" => {
+                let address = self.heap_address(segment, position);
+                self.heap.insert(address, value);
+            }
+            "constant" => return Err("cannot pop into the constant segment".to_string()),
+            _ => return Err(format!("unknown segment {segment}")),
+        }
+        Ok(())
+    }
+
+    /// Returns the RAM address of `this[index]` or `that[index]`.
+    fn heap_address(&self, segment: &str, index: usize) -> usize {
+        let base = self.pointer[usize::from(segment == "that")];
+        (base as u16 as usize).wrapping_add(index) & 0x7fff
+    }
+}
+
+/// Computes a binary arithmetic or logical command with 16-bit two's-complement semantics.
+///
+/// Comparisons produce `-1` (true) or `0` (false). `gt` and `lt` test the sign of the wrapped
+/// difference `x - y`, like the `D=M-D` / `D;JGT` code the translator emits, so they are wrong
+/// when the subtraction overflows: `32767 gt -1` is false.
+pub fn arithmetic(command: &str, x: i16, y: i16) -> i16 {
+    match command {
+        "add" => x.wrapping_add(y),
+        "sub" => x.wrapping_sub(y),
+        "and" => x & y,
+        "or" => x | y,
+        "eq" => -i16::from(x == y),
+        "gt" => -i16::from(x.wrapping_sub(y) > 0),
+        "lt" => -i16::from(x.wrapping_sub(y) < 0),
+        _ => panic!("Command {command} is not a binary arithmetic command"),
+    }
+}
+
+/// Returns `values[index]`, growing `values` with zeros when needed.
+fn slot(values: &mut Vec<i16>, index: usize) -> &mut i16 {
+    if values.len() <= index {
+        values.resize(index + 1, 0);
+    }
+    &mut values[index]
+}
+
+/// Parses the argument or local count of a function command.
+fn parse_count(part: Option<&str>) -> Result<usize, String> {
+    part.and_then(|n| n.parse().ok())
+        .ok_or_else(|| "missing or invalid count".to_string())
+}
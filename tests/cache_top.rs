@@ -0,0 +1,87 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::program::*;
+
+fn cache_top() -> CodegenOptions {
+    CodegenOptions {
+        cache_top: true,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn fixtures_give_the_expected_ram() {
+    let both = CodegenOptions {
+        tail_calls: true,
+        cache_top: true,
+        ..Default::default()
+    };
+    for options in [cache_top(), both] {
+        for name in FIXTURES {
+            assert_fixture_ram(name, translate_fixture("cache_top", name, &options).rom);
+        }
+    }
+}
+
+#[test]
+fn saves_instructions() {
+    for name in FIXTURES {
+        let plain = translate_fixture("cache_top", name, &CodegenOptions::default())
+            .rom
+            .len();
+        let cached = translate_fixture("cache_top", name, &cache_top()).rom.len();
+        assert!(cached * 10 < plain * 9, "{name}: {cached} of {plain}");
+    }
+    // `push constant 7; push constant 8; add; pop temp 0` keeps both sums in D.
+    let dir = write_program(
+        "cache_top_sum",
+        &[(
+            "Main.vm",
+            "push constant 7\npush constant 8\nadd\npop temp 0\n",
+        )],
+    );
+    let output = format!("{dir}/Out.asm");
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.options = cache_top();
+    for instruction in &ProgramClass::new(vec![dir]).instructions {
+        writer.write_instruction(instruction);
+    }
+    writer.flush();
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    // `@7 D=A`, the spill of 7 when 8 is pushed, `@8 D=A`, `@SP AM=M-1 D=D+M` and `@5 M=D`.
+    assert_eq!(assembler.rom.len(), 2 + 5 + 2 + 3 + 2);
+}
+
+#[test]
+fn random_programs_match_interpreter() {
+    let both = CodegenOptions {
+        tail_calls: true,
+        cache_top: true,
+        ..Default::default()
+    };
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("cache_top_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        for (options, output) in [(cache_top(), "Cached.asm"), (both.clone(), "Both.asm")] {
+            let mut harness = DifferentialClass::from_programs(
+                &program,
+                &program,
+                &options,
+                format!("{dir}/{output}"),
+            );
+            harness
+                .run(200_000)
+                .unwrap_or_else(|error| panic!("seed {seed} {output}: {error}"));
+        }
+    }
+}
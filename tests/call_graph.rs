@@ -0,0 +1,116 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::call_graph::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::vm_interpreter::*;
+
+const SYS: &str = "function Sys.init 0
+call Main.main 0
+pop static 0
+label END
+goto END";
+
+const MAIN: &str = "function Main.main 0
+push constant 3
+call Math.double 1
+return
+function Main.unused 0
+call Math.triple 1
+return";
+
+const MATH: &str = "function Math.double 0
+push argument 0
+push argument 0
+add
+return
+function Math.triple 0
+push argument 0
+push argument 0
+push argument 0
+add
+add
+return";
+
+fn program(name: &str) -> ProgramClass {
+    let files = [("Sys.vm", SYS), ("Main.vm", MAIN), ("Math.vm", MATH)];
+    ProgramClass::new(vec![write_program(name, &files)])
+}
+
+#[test]
+fn finds_unreachable_functions() {
+    let program = program("call_graph_unreachable");
+    let graph = CallGraphClass::new(&program);
+
+    let reachable: Vec<String> = graph.reachable(ENTRY_POINT).into_iter().collect();
+    assert_eq!(reachable, ["Main.main", "Math.double", "Sys.init"]);
+    let unreachable: Vec<String> = graph
+        .unreachable(ENTRY_POINT)
+        .into_iter()
+        .map(|function| function.name)
+        .collect();
+    assert_eq!(unreachable, ["Main.unused", "Math.triple"]);
+
+    let warnings: Vec<String> = graph
+        .check(&program, ENTRY_POINT)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        warnings,
+        [
+            "Main.vm:5: warning: function Main.unused is never called from Sys.init",
+            "Math.vm:6: warning: function Math.triple is never called from Sys.init",
+        ]
+    );
+
+    // Without the entry point nothing is known to be unreachable.
+    assert!(graph.unreachable("Main.start").is_empty());
+    assert_eq!(graph.unreachable("Main.unused").len(), 3);
+}
+
+#[test]
+fn pruned_programs_behave_the_same() {
+    let program = program("call_graph_prune");
+    let pruned = CallGraphClass::new(&program).prune(&program, ENTRY_POINT);
+
+    let names: Vec<String> = pruned
+        .functions()
+        .into_iter()
+        .map(|function| function.name)
+        .collect();
+    assert_eq!(names, ["Main.main", "Math.double", "Sys.init"]);
+    assert_eq!(pruned.instructions.len(), program.instructions.len() - 10);
+
+    let mut full = InterpreterClass::new(&program);
+    let mut reduced = InterpreterClass::new(&pruned);
+    full.run(1000);
+    reduced.run(1000);
+    assert!(full.halted && reduced.halted);
+    assert_eq!(full.statics, reduced.statics);
+    assert_eq!(reduced.statics["Sys.0"], 6);
+}
+
+#[test]
+fn pruning_keeps_the_bootstrap_entry_point() {
+    let program = program("call_graph_prune_entry");
+    let pruned = CallGraphClass::new(&program).prune(&program, "Main.unused");
+
+    // Sys.init is called by the bootstrap code even when the call graph starts elsewhere.
+    let names: Vec<String> = pruned
+        .functions()
+        .into_iter()
+        .map(|function| function.name)
+        .collect();
+    assert_eq!(
+        names,
+        [
+            "Main.main",
+            "Main.unused",
+            "Math.double",
+            "Math.triple",
+            "Sys.init"
+        ]
+    );
+    assert_eq!(pruned.instructions.len(), program.instructions.len());
+}
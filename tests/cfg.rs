@@ -0,0 +1,59 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::cfg::*;
+use virtual_machine_translator::utils::program::*;
+
+fn cfg(name: &str, source: &str) -> CfgClass {
+    let program = ProgramClass::new(vec![write_program(name, &[("Main.vm", source)])]);
+    CfgClass::new(&program, &program.functions()[0])
+}
+
+#[test]
+fn splits_blocks_at_labels_and_branches() {
+    let cfg = cfg(
+        "cfg_loop",
+        "function Main.loop 1
+label LOOP
+push local 0
+if-goto END
+call Main.tick 0
+pop temp 0
+goto LOOP
+label END
+push constant 0
+return",
+    );
+    let ranges: Vec<(usize, usize)> = cfg.blocks.iter().map(|b| (b.start, b.end)).collect();
+    assert_eq!(ranges, [(0, 1), (1, 4), (4, 5), (5, 7), (7, 10)]);
+
+    let edges: Vec<Vec<Edge>> = cfg.blocks.iter().map(|b| b.edges.clone()).collect();
+    assert_eq!(
+        edges,
+        [
+            vec![Edge::FallThrough(1)],
+            vec![Edge::Branch(4), Edge::FallThrough(2)],
+            vec![Edge::Call("Main.tick".to_string(), 3)],
+            vec![Edge::Goto(1)],
+            vec![Edge::Return],
+        ]
+    );
+    assert_eq!(cfg.block_of(6), Some(3));
+}
+
+#[test]
+fn writes_dot() {
+    let cfg = cfg(
+        "cfg_dot",
+        "function Main.f 0\npush argument 0\nif-goto \"X\"\nlabel \"X\"\nreturn",
+    );
+    let dot = cfg.to_dot();
+
+    assert!(dot.starts_with("digraph \"Main.f\" {\n"));
+    assert!(dot.contains(
+        "  b0 [label=\"1: function Main.f 0\\l2: push argument 0\\l3: if-goto \\\"X\\\"\\l\"];\n"
+    ));
+    assert!(dot.contains("  b0 -> b1 [label=\"if-goto\"];\n  b0 -> b1;\n"));
+    assert!(dot.contains("  return [shape=oval];\n"));
+    assert!(dot.ends_with("  b1 -> return;\n}\n"));
+}
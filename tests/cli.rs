@@ -0,0 +1,32 @@
+mod common;
+
+use common::*;
+use std::fs;
+use std::process::Command;
+use virtual_machine_translator::utils::assembler::*;
+
+/// Runs the translator on a fixture and returns the assembled output.
+fn translate(name: &str) -> AssemblerClass {
+    let output = scratch_dir(&format!("cli_{name}")).join("Out.asm");
+    let status = Command::new(env!("CARGO_BIN_EXE_vmtranslator"))
+        .arg(fixture_dir(name))
+        .arg(&output)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{name}: {status}");
+    AssemblerClass::new(&fs::read_to_string(output).unwrap())
+}
+
+#[test]
+fn programs_without_sys_init_start_at_their_first_command() {
+    let assembler = translate("SimpleAdd");
+    assert!(!assembler.variables.contains_key("Sys.init"));
+    assert_fixture_ram("SimpleAdd", assembler.rom);
+}
+
+#[test]
+fn programs_with_sys_init_start_with_the_bootstrap_code() {
+    let assembler = translate("FibonacciElement");
+    assert!(assembler.labels.contains_key("Sys.init"));
+    assert_fixture_ram("FibonacciElement", assembler.rom);
+}
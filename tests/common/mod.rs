@@ -0,0 +1,364 @@
+#![allow(dead_code)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::program::*;
+
+/// The number of Hack instructions a fixture may execute before it is considered stuck.
+pub const MAX_CYCLES: usize = 1_000_000;
+
+/// The fixtures of `tests/fixtures`, each with a `setup.ram` and an `expected.ram`.
+pub const FIXTURES: [&str; 11] = [
+    "SimpleAdd",
+    "StackTest",
+    "BasicTest",
+    "PointerTest",
+    "StaticTest",
+    "BasicLoop",
+    "FibonacciSeries",
+    "SimpleFunction",
+    "NestedCall",
+    "FibonacciElement",
+    "StaticsTest",
+];
+
+/// Creates an empty scratch directory for a test.
+pub fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("vm_translator_{}_{name}", std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes VM files into a new scratch directory and returns its path.
+pub fn write_program(name: &str, files: &[(&str, &str)]) -> String {
+    let dir = scratch_dir(name);
+    for (file, source) in files {
+        fs::write(dir.join(file), source).unwrap();
+    }
+    dir.to_string_lossy().to_string()
+}
+
+/// Returns the directory of a fixture.
+pub fn fixture_dir(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+/// Reads a `.ram` file made of `<address> <value>` lines.
+pub fn read_ram(path: &Path) -> Vec<(usize, i16)> {
+    fs::read_to_string(path)
+        .unwrap()
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let (address, value) = line.trim().split_once(' ').unwrap();
+            (address.parse().unwrap(), value.parse().unwrap())
+        })
+        .collect()
+}
+
+/// Translates a fixture like the golden tests, the bootstrap code only for programs with `Sys.vm`,
+/// into the scratch directory `<test>_<name>` and assembles the result.
+pub fn translate_fixture(test: &str, name: &str, options: &CodegenOptions) -> AssemblerClass {
+    let fixture = fixture_dir(name);
+    let program = ProgramClass::new(vec![fixture.to_string_lossy().to_string()]);
+    let output = scratch_dir(&format!("{test}_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.options = options.clone();
+    if fixture.join("Sys.vm").exists() {
+        writer.write_init();
+    }
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    writer.flush();
+    AssemblerClass::new(&fs::read_to_string(output).unwrap())
+}
+
+/// Runs `rom` from the `setup.ram` of a fixture until it halts, then checks every address of its
+/// `expected.ram`, returning the emulator for further checks.
+pub fn assert_fixture_ram(name: &str, rom: Vec<u16>) -> EmulatorClass {
+    assert_fixture_ram_where(name, rom, |_| true)
+}
+
+/// Like `assert_fixture_ram`, only checking the addresses of `expected.ram` that `checked` accepts.
+pub fn assert_fixture_ram_where(
+    name: &str,
+    rom: Vec<u16>,
+    checked: impl Fn(usize) -> bool,
+) -> EmulatorClass {
+    let fixture = fixture_dir(name);
+    let mut emulator = EmulatorClass::new(rom);
+    for (address, value) in read_ram(&fixture.join("setup.ram")) {
+        emulator.ram[address] = value;
+    }
+    emulator.run(MAX_CYCLES);
+    assert!(emulator.is_halted(), "{name} did not halt");
+    for (address, value) in read_ram(&fixture.join("expected.ram")) {
+        if checked(address) {
+            assert_eq!(emulator.ram[address], value, "{name}: RAM[{address}]");
+        }
+    }
+    emulator
+}
+
+/// A small deterministic pseudo random number generator (SplitMix64).
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in `0..n`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn chance(&mut self, percent: usize) -> bool {
+        self.below(100) < percent
+    }
+}
+
+/// Describes a generated function.
+struct Signature {
+    name: String,
+    file: usize,
+    n_args: usize,
+    /// Locals usable by random statements. One more local is reserved as loop counter.
+    n_vars: usize,
+}
+
+/// Generates random well-formed VM programs.
+///
+/// Generated programs always terminate: functions only call functions declared after
+/// them, loops are counted, and `Sys.init` ends with the `label END; goto END` loop.
+/// Every branch leaves the stack at the height it had before the branch.
+pub struct Generator {
+    rng: Rng,
+    signatures: Vec<Signature>,
+    labels: usize,
+    output: Vec<String>,
+}
+
+const FILES: [&str; 2] = ["Main", "Util"];
+
+impl Generator {
+    pub fn new(seed: u64) -> Self {
+        Generator {
+            rng: Rng::new(seed),
+            signatures: Vec::new(),
+            labels: 0,
+            output: Vec::new(),
+        }
+    }
+
+    /// Generates a program and returns its files as `(file name, source)`.
+    pub fn generate(mut self) -> Vec<(String, String)> {
+        let count = 2 + self.rng.below(5);
+        for k in 0..count {
+            let file = self.rng.below(FILES.len());
+            self.signatures.push(Signature {
+                name: format!("{}.f{k}", FILES[file]),
+                file,
+                n_args: self.rng.below(4),
+                n_vars: self.rng.below(4),
+            });
+        }
+
+        let mut files: Vec<(String, String)> = Vec::new();
+
+        // The entry point sets the heap pointers and calls the first function.
+        let first = &self.signatures[0];
+        let mut sys = String::from("// Generated entry point\nfunction Sys.init 0\n");
+        sys += "push constant 3000\npop pointer 0\npush constant 4000\npop pointer 1\n";
+        for i in 0..first.n_args {
+            sys += &format!("push constant {}\n", 10 + i);
+        }
+        sys += &format!("call {} {}\npop temp 0\n", first.name, first.n_args);
+        sys += "label Sys.init_END\ngoto Sys.init_END\n";
+        files.push(("Sys.vm".to_string(), sys));
+
+        for (file, module) in FILES.iter().enumerate() {
+            let mut source = String::new();
+            for k in 0..self.signatures.len() {
+                if self.signatures[k].file == file {
+                    self.output.clear();
+                    self.function(k);
+                    source += &self.output.join("\n");
+                    source += "\n";
+                }
+            }
+            files.push((format!("{module}.vm"), source));
+        }
+        files
+    }
+
+    /// Returns a new label of the function. Every function numbers its labels from 1, so
+    /// functions declare the same labels.
+    fn label(&mut self) -> String {
+        self.labels += 1;
+        format!("L{}", self.labels)
+    }
+
+    /// Returns a random constant push, now and then near the ends of the 16-bit range where
+    /// additions and comparisons overflow.
+    fn constant(&mut self) -> String {
+        match self.rng.below(10) {
+            0 => format!("push constant {}", 32767 - self.rng.below(4)),
+            1 => format!("push constant {}\nnot", 32767 - self.rng.below(4)),
+            _ => format!("push constant {}", self.rng.below(101)),
+        }
+    }
+
+    fn function(&mut self, k: usize) {
+        self.labels = 0;
+        let counter = self.signatures[k].n_vars;
+        self.output.push(format!(
+            "function {} {}",
+            self.signatures[k].name,
+            counter + 1
+        ));
+        let mut depth = 0;
+        let count = 3 + self.rng.below(12);
+        for _ in 0..count {
+            self.statement(k, &mut depth, 0, 2, true);
+        }
+        if depth == 0 || self.rng.chance(20) {
+            self.push_any(k);
+        }
+        self.output.push("return".to_string());
+    }
+
+    /// Emits a random push of any readable segment.
+    fn push_any(&mut self, k: usize) {
+        let n_args = self.signatures[k].n_args;
+        let n_vars = self.signatures[k].n_vars;
+        let line = match self.rng.below(8) {
+            1 if n_vars > 0 => format!("push local {}", self.rng.below(n_vars)),
+            2 if n_args > 0 => format!("push argument {}", self.rng.below(n_args)),
+            3 => format!("push static {}", self.rng.below(4)),
+            4 => format!("push temp {}", self.rng.below(7)),
+            5 => format!("push this {}", self.rng.below(8)),
+            6 => format!("push that {}", self.rng.below(8)),
+            7 => format!("push pointer {}", self.rng.below(2)),
+            _ => self.constant(),
+        };
+        self.output.push(line);
+    }
+
+    /// Emits a random pop into any writable segment.
+    fn pop_any(&mut self, k: usize) {
+        let n_args = self.signatures[k].n_args;
+        let n_vars = self.signatures[k].n_vars;
+        let line = match self.rng.below(6) {
+            1 if n_vars > 0 => format!("pop local {}", self.rng.below(n_vars)),
+            2 if n_args > 0 => format!("pop argument {}", self.rng.below(n_args)),
+            3 => format!("pop static {}", self.rng.below(4)),
+            4 => format!("pop this {}", self.rng.below(8)),
+            5 => format!("pop that {}", self.rng.below(8)),
+            _ => format!("pop temp {}", self.rng.below(7)),
+        };
+        self.output.push(line);
+    }
+
+    /// Emits a random statement, never popping the stack below `floor`.
+    fn statement(
+        &mut self,
+        k: usize,
+        depth: &mut usize,
+        floor: usize,
+        nesting: usize,
+        loops: bool,
+    ) {
+        match self.rng.below(10) {
+            0..=2 => {
+                self.push_any(k);
+                *depth += 1;
+            }
+            3 if *depth > floor => {
+                self.pop_any(k);
+                *depth -= 1;
+            }
+            4 | 5 if *depth >= floor + 2 => {
+                let ops = ["add", "sub", "and", "or", "eq", "gt", "lt"];
+                self.output.push(ops[self.rng.below(ops.len())].to_string());
+                *depth -= 1;
+            }
+            6 if *depth > floor => {
+                let ops = ["neg", "not"];
+                self.output.push(ops[self.rng.below(ops.len())].to_string());
+            }
+            7 if k + 1 < self.signatures.len() => {
+                let callee = k + 1 + self.rng.below(self.signatures.len() - k - 1);
+                let n_args = self.signatures[callee].n_args;
+                for _ in 0..n_args {
+                    self.push_any(k);
+                }
+                self.output
+                    .push(format!("call {} {n_args}", self.signatures[callee].name));
+                *depth += 1;
+            }
+            8 if nesting > 0 => {
+                // if-goto over a block that leaves the stack unchanged.
+                let skip = self.label();
+                self.push_any(k);
+                self.output.push(format!("if-goto {skip}"));
+                self.block(k, *depth, nesting - 1, loops);
+                self.output.push(format!("label {skip}"));
+            }
+            9 if nesting > 0 && loops => {
+                // Counted loop using the reserved local.
+                let counter = self.signatures[k].n_vars;
+                let (top, end) = (self.label(), self.label());
+                self.output
+                    .push(format!("push constant {}", self.rng.below(4)));
+                self.output.push(format!("pop local {counter}"));
+                self.output.push(format!("label {top}"));
+                self.output.push(format!("push local {counter}"));
+                self.output.push("push constant 0".to_string());
+                self.output.push("eq".to_string());
+                self.output.push(format!("if-goto {end}"));
+                self.block(k, *depth, nesting - 1, false);
+                self.output.push(format!("push local {counter}"));
+                self.output.push("push constant 1".to_string());
+                self.output.push("sub".to_string());
+                self.output.push(format!("pop local {counter}"));
+                self.output.push(format!("goto {top}"));
+                self.output.push(format!("label {end}"));
+            }
+            _ => {
+                let line = self.constant();
+                self.output.push(line);
+                *depth += 1;
+            }
+        }
+    }
+
+    /// Emits random statements starting and ending at stack height `depth`.
+    fn block(&mut self, k: usize, depth: usize, nesting: usize, loops: bool) {
+        let mut inner = depth;
+        for _ in 0..1 + self.rng.below(5) {
+            self.statement(k, &mut inner, depth, nesting, loops);
+        }
+        while inner > depth {
+            self.pop_any(k);
+            inner -= 1;
+        }
+    }
+}
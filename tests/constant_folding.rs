@@ -0,0 +1,226 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::constant_folding::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::program::*;
+
+/// Folds a single-file program and returns the folding pass and the folded commands.
+fn fold(name: &str, source: &str) -> (ConstantFoldingClass, Vec<String>) {
+    let program = ProgramClass::new(vec![write_program(
+        &format!("constant_folding_{name}"),
+        &[("Main.vm", source)],
+    )]);
+    let mut pass = ConstantFoldingClass::new(&program);
+    let folded = pass.fold();
+    let commands = folded
+        .instructions
+        .iter()
+        .map(|instruction| instruction.current_command.clone())
+        .collect();
+    (pass, commands)
+}
+
+#[test]
+fn folds_arithmetic_comparisons_and_logic() {
+    let (pass, commands) = fold(
+        "expressions",
+        "function Main.main 0
+push constant 2
+push constant 3
+add
+push constant 7
+push constant 2
+sub
+push constant 3
+gt
+push constant 12
+push constant 10
+and
+push constant 1
+neg
+return",
+    );
+    assert_eq!(
+        commands,
+        [
+            "function Main.main 0",
+            "push constant 5",
+            "push constant 0",
+            "not",
+            "push constant 8",
+            "push constant 0",
+            "not",
+            "return"
+        ]
+    );
+    assert_eq!(pass.folded, 5);
+    assert_eq!(pass.removed, 7);
+}
+
+#[test]
+fn uses_16_bit_twos_complement() {
+    let (_, commands) = fold(
+        "wrapping",
+        "push constant 32767
+push constant 1
+add
+push constant 32767
+push constant 1
+neg
+gt
+push constant 0
+push constant 32767
+sub
+push constant 1
+sub
+neg",
+    );
+    // 32767 + 1 wraps to -32768, 32767 - -1 wraps to -32768 so `gt` is false, and -(-32768) wraps to -32768.
+    assert_eq!(
+        commands,
+        [
+            "push constant 32767",
+            "not",
+            "push constant 0",
+            "push constant 32767",
+            "not"
+        ]
+    );
+}
+
+#[test]
+fn keeps_unknown_values_and_jump_targets() {
+    let source = "function Main.main 1
+push constant 0
+not
+push local 0
+push constant 1
+add
+push constant 1
+label LOOP
+push constant 2
+add
+push constant 40000
+push constant 1
+add
+return";
+    let (pass, commands) = fold("unknown", source);
+    let expected: Vec<&str> = source.lines().collect();
+    assert_eq!(commands, expected);
+    assert_eq!(pass.folded, 0);
+}
+
+#[test]
+fn folded_commands_keep_the_operator_location() {
+    let program = ProgramClass::new(vec![write_program(
+        "constant_folding_location",
+        &[("Main.vm", "push constant 1\npush constant 2\n\nadd\n")],
+    )]);
+    let folded = ConstantFoldingClass::new(&program).fold();
+    assert_eq!(folded.instructions.len(), 1);
+    assert_eq!(folded.instructions[0].file_name, "Main.vm");
+    assert_eq!(folded.instructions[0].line, 4);
+    assert_eq!(folded.instructions[0].index, Some(3));
+}
+
+#[test]
+fn overflowing_comparisons_match_the_translated_code() {
+    let main = "function Main.compare 0
+push constant 32767
+push constant 1
+neg
+gt
+pop static 0
+push constant 32767
+push constant 1
+neg
+lt
+pop static 1
+push constant 32767
+neg
+push constant 2
+gt
+pop static 2
+push argument 0
+push argument 1
+gt
+if-goto WRAPPED
+push constant 0
+return
+label WRAPPED
+push constant 1
+return";
+    let sys = "function Sys.init 0
+push constant 32767
+push constant 1
+neg
+call Main.compare 2
+pop static 3
+label END
+goto END";
+    let dir = write_program(
+        "constant_folding_overflow",
+        &[("Main.vm", main), ("Sys.vm", sys)],
+    );
+    let program = ProgramClass::new(vec![dir.clone()]);
+    let mut pass = ConstantFoldingClass::new(&program);
+    let optimised = pass.fold();
+    assert_eq!(pass.folded, 7);
+
+    // The templates, the compare cached in D and the compare fused with `if-goto` all wrap.
+    let options = [
+        CodegenOptions::default(),
+        CodegenOptions {
+            cache_top: true,
+            ..Default::default()
+        },
+        CodegenOptions {
+            specialise: true,
+            ..Default::default()
+        },
+    ];
+    for (number, options) in options.iter().enumerate() {
+        for translated in [&program, &optimised] {
+            let mut harness = DifferentialClass::from_programs(
+                &program,
+                translated,
+                options,
+                format!("{dir}/Out{number}.asm"),
+            );
+            let compared = harness
+                .run(10_000)
+                .unwrap_or_else(|error| panic!("{options:?}: {error}"));
+            assert_eq!(compared, 1);
+        }
+    }
+}
+
+#[test]
+fn folded_programs_match_interpreter() {
+    let mut folded = 0;
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("constant_folding_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        let mut pass = ConstantFoldingClass::new(&program);
+        let optimised = pass.fold();
+        folded += pass.folded;
+
+        let mut harness = DifferentialClass::from_programs(
+            &program,
+            &optimised,
+            &CodegenOptions::default(),
+            format!("{dir}/Out.asm"),
+        );
+        harness
+            .run(200_000)
+            .unwrap_or_else(|error| panic!("seed {seed}: {error}"));
+    }
+    assert!(folded > 0);
+}
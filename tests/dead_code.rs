@@ -0,0 +1,202 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::dead_code::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::program::*;
+
+/// Runs the pass over a single-file program and returns it and the commands left.
+fn eliminate(name: &str, source: &str) -> (DeadCodeClass, Vec<String>) {
+    let program = ProgramClass::new(vec![write_program(
+        &format!("dead_code_{name}"),
+        &[("Main.vm", source)],
+    )]);
+    let mut pass = DeadCodeClass::new(&program);
+    let eliminated = pass.eliminate();
+    let commands = eliminated
+        .instructions
+        .iter()
+        .map(|instruction| instruction.current_command.clone())
+        .collect();
+    (pass, commands)
+}
+
+#[test]
+fn removes_code_after_goto_and_return() {
+    let (pass, commands) = eliminate(
+        "after_jumps",
+        "function Main.main 1
+push constant 1
+pop local 0
+goto END
+push constant 2
+pop local 0
+label DEAD
+push constant 3
+pop local 0
+label END
+push local 0
+return
+push constant 4
+return
+function Main.other 0
+push constant 5
+return",
+    );
+    assert_eq!(
+        commands,
+        [
+            "function Main.main 1",
+            "push constant 1",
+            "pop local 0",
+            "goto END",
+            "label END",
+            "push local 0",
+            "return",
+            "function Main.other 0",
+            "push constant 5",
+            "return"
+        ]
+    );
+    assert_eq!(pass.removed, 6);
+    assert_eq!(pass.labels, 1);
+}
+
+#[test]
+fn keeps_loops_branches_and_calls() {
+    let source = "function Main.main 1
+label LOOP
+push local 0
+push constant 10
+lt
+if-goto BODY
+goto DONE
+label BODY
+push local 0
+call Main.next 1
+pop local 0
+goto LOOP
+label DONE
+push local 0
+return";
+    let (pass, commands) = eliminate("loops", source);
+    let lines: Vec<&str> = source.lines().collect();
+    assert_eq!(commands, lines);
+    assert_eq!((pass.removed, pass.labels), (0, 0));
+}
+
+#[test]
+fn removes_labels_nothing_jumps_to() {
+    let (pass, commands) = eliminate(
+        "labels",
+        "function Main.main 0
+label UNUSED
+push constant 1
+if-goto USED
+label ONLY_FROM_DEAD
+label USED
+push constant 2
+return
+goto ONLY_FROM_DEAD",
+    );
+    assert_eq!(
+        commands,
+        [
+            "function Main.main 0",
+            "push constant 1",
+            "if-goto USED",
+            "label USED",
+            "push constant 2",
+            "return"
+        ]
+    );
+    assert_eq!((pass.removed, pass.labels), (1, 2));
+}
+
+#[test]
+fn keeps_unreachable_labels_other_functions_jump_to() {
+    let (pass, commands) = eliminate(
+        "shared_label",
+        "function Main.main 0
+push constant 1
+return
+label SHARED
+push constant 2
+return
+function Main.other 0
+goto SHARED",
+    );
+    assert_eq!(
+        commands,
+        [
+            "function Main.main 0",
+            "push constant 1",
+            "return",
+            "label SHARED",
+            "function Main.other 0",
+            "goto SHARED"
+        ]
+    );
+    assert_eq!((pass.removed, pass.labels), (2, 0));
+}
+
+#[test]
+fn leaves_unknown_control_flow_and_top_level_code() {
+    // `MISSING` is not declared by the function, so nothing of it is removed.
+    let source = "push constant 1
+goto AFTER
+push constant 2
+label AFTER
+function Main.main 0
+goto MISSING
+push constant 3
+label UNUSED
+return";
+    let (pass, commands) = eliminate("unknown", source);
+    let lines: Vec<&str> = source.lines().collect();
+    assert_eq!(commands, lines);
+    assert_eq!((pass.removed, pass.labels), (0, 0));
+}
+
+#[test]
+fn kept_commands_are_unchanged() {
+    let program = ProgramClass::new(vec![write_program(
+        "dead_code_unchanged",
+        &[(
+            "Main.vm",
+            "function Main.main 0\npush constant 7\nreturn\npush constant 8\nreturn\n",
+        )],
+    )]);
+    let eliminated = DeadCodeClass::new(&program).eliminate();
+    assert_eq!(eliminated.instructions.len(), 3);
+    for (kept, original) in eliminated.instructions.iter().zip(&program.instructions) {
+        assert_eq!(kept.current_command, original.current_command);
+        assert_eq!(kept.line, original.line);
+        assert_eq!(kept.file_name, original.file_name);
+    }
+}
+
+#[test]
+fn eliminated_programs_match_interpreter() {
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("dead_code_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        let optimised = DeadCodeClass::new(&program).eliminate();
+
+        let mut harness = DifferentialClass::from_programs(
+            &program,
+            &optimised,
+            &CodegenOptions::default(),
+            format!("{dir}/Out.asm"),
+        );
+        harness
+            .run(200_000)
+            .unwrap_or_else(|error| panic!("seed {seed}: {error}"));
+    }
+}
@@ -0,0 +1,87 @@
+use std::io::Cursor;
+use virtual_machine_translator::utils::debugger::*;
+use virtual_machine_translator::utils::program::*;
+
+fn debugger(name: &str) -> DebuggerClass {
+    let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    DebuggerClass::new(&ProgramClass::new(vec![fixture]))
+}
+
+#[test]
+fn stops_at_breakpoints() {
+    let mut debugger = debugger("NestedCall");
+    assert_eq!(
+        debugger.execute("break Sys.add12"),
+        "Breakpoint 1 at Sys.add12"
+    );
+    assert_eq!(debugger.execute("break Sys:32"), "Breakpoint 2 at Sys:32");
+
+    assert_eq!(
+        debugger.execute("continue"),
+        "Breakpoint 1, Sys.vm:39: function Sys.add12 0"
+    );
+    assert_eq!(debugger.execute("print argument"), "[0] 123");
+    assert_eq!(
+        debugger.execute("backtrace"),
+        "#0 Sys.add12 at Sys.vm:39\n#1 Sys.main at Sys.vm:25\n#2 Sys.init at Sys.vm:7\n#3 <top level>"
+    );
+    assert_eq!(debugger.execute("continue"), "Breakpoint 2, Sys.vm:32: add");
+    assert_eq!(
+        debugger.execute("print stack"),
+        "[0] 0\n[1] 200\n[2] 40\n[3] 6\n[4] 0"
+    );
+
+    debugger.execute("delete 2");
+    assert_eq!(debugger.execute("info breakpoints"), "1: Sys.add12");
+    assert!(debugger.execute("continue").starts_with("Program halted"));
+}
+
+#[test]
+fn stops_at_a_breakpoint_on_the_first_command() {
+    let mut debugger = debugger("NestedCall");
+    debugger.execute("break Sys.init");
+    assert_eq!(
+        debugger.execute("continue"),
+        "Breakpoint 1, Sys.vm:2: function Sys.init 0"
+    );
+    assert!(debugger.execute("continue").starts_with("Program halted"));
+}
+
+#[test]
+fn steps_over_and_out_of_calls() {
+    let mut debugger = debugger("NestedCall");
+    debugger.execute("break Sys.vm:25");
+    debugger.execute("continue");
+
+    // next runs the whole call of Sys.add12.
+    assert_eq!(debugger.execute("next"), "Sys.vm:26: pop temp 0");
+    assert_eq!(debugger.execute("print stack"), "[0] 135");
+
+    // finish returns to Sys.init, which then stores the result.
+    assert_eq!(debugger.execute("finish"), "Sys.vm:8: pop temp 1");
+    assert_eq!(debugger.execute("step"), "Sys.vm:9: label LOOP");
+    assert_eq!(
+        debugger.execute("print temp").lines().nth(1),
+        Some("[1] 246")
+    );
+    assert_eq!(debugger.execute("print that 1"), "[0] 0   (RAM[5000])");
+}
+
+#[test]
+fn runs_scripts() {
+    let mut debugger = debugger("StaticsTest");
+    let script =
+        "# Stop in the second class.\nbreak Class2.get\n\ncontinue\nprint static\nquit\nstep\n";
+
+    let mut output: Vec<u8> = Vec::new();
+    debugger.run_script(&mut Cursor::new(script), &mut output);
+    let output = String::from_utf8(output).unwrap();
+
+    assert!(debugger.quit);
+    assert!(output
+        .starts_with("(vmdb) break Class2.get\nBreakpoint 1 at Class2.get\n(vmdb) continue\n"));
+    assert!(
+        output.ends_with("(vmdb) print static\n[0] 23\n[1] 15\n(vmdb) quit\n"),
+        "{output}"
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::differential::*;
+
+fn check(name: &str, files: &[(&str, &str)]) -> usize {
+    let dir = write_program(name, files);
+    let output = format!("{dir}/Out.asm");
+    let mut harness = DifferentialClass::new(vec![dir], output);
+    harness
+        .run(200_000)
+        .unwrap_or_else(|error| panic!("{name}: {error}"))
+}
+
+#[test]
+fn recursive_fibonacci_matches_interpreter() {
+    let main = "function Main.fibonacci 0
+push argument 0
+push constant 2
+lt
+if-goto N_LT_2
+push argument 0
+push constant 2
+sub
+call Main.fibonacci 1
+push argument 0
+push constant 1
+sub
+call Main.fibonacci 1
+add
+return
+label N_LT_2
+push argument 0
+return";
+    let sys = "function Sys.init 0
+push constant 6
+call Main.fibonacci 1
+pop static 0
+label END
+goto END";
+    let compared = check("fibonacci", &[("Main.vm", main), ("Sys.vm", sys)]);
+    assert_eq!(compared, 25);
+}
+
+#[test]
+fn statics_are_separate_per_file() {
+    let a = "function A.set 0
+push argument 0
+pop static 0
+push constant 0
+return";
+    let b = "function B.set 0
+push argument 0
+pop static 0
+push constant 0
+return";
+    let sys = "function Sys.init 0
+push constant 11
+call A.set 1
+push constant 22
+call B.set 1
+label END
+goto END";
+    let compared = check("statics", &[("A.vm", a), ("B.vm", b), ("Sys.vm", sys)]);
+    assert_eq!(compared, 2);
+}
+
+#[test]
+fn pointers_are_restored_on_return() {
+    let main = "function Main.clobber 0
+push constant 4000
+pop pointer 0
+push constant 5000
+pop pointer 1
+push constant 0
+return
+function Main.identity 0
+push argument 0
+return";
+    let sys = "function Sys.init 0
+push constant 3000
+pop pointer 0
+push constant 3010
+pop pointer 1
+call Main.clobber 0
+pop temp 0
+push pointer 0
+push pointer 1
+add
+call Main.identity 1
+pop static 0
+label END
+goto END";
+    let compared = check("pointers", &[("Main.vm", main), ("Sys.vm", sys)]);
+    assert_eq!(compared, 2);
+}
+
+#[test]
+fn random_programs_match_interpreter() {
+    for seed in 0..40 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let compared = check(&format!("random_{seed}"), &files);
+        assert!(compared > 0, "seed {seed} never returned");
+    }
+}
@@ -0,0 +1,68 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::disassembler::*;
+use virtual_machine_translator::utils::program::*;
+
+fn assemble(name: &str) -> AssemblerClass {
+    let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    let output = scratch_dir(&format!("disassembler_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+
+    let program = ProgramClass::new(vec![fixture]);
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    AssemblerClass::new(&fs::read_to_string(output).unwrap())
+}
+
+#[test]
+fn decodes_computations() {
+    let assembler = AssemblerClass::new("AM=M+1\nD;JGT\n0;JMP\nMD=D|M\nAMD=!A;JLE\n");
+    let decoded: Vec<String> = assembler
+        .rom
+        .iter()
+        .map(|word| decode(*word).unwrap())
+        .collect();
+    assert_eq!(
+        decoded,
+        ["AM=M+1", "D;JGT", "0;JMP", "MD=D|M", "AMD=!A;JLE"]
+    );
+}
+
+#[test]
+fn round_trips_without_symbols() {
+    for name in ["StackTest", "FibonacciElement"] {
+        let assembler = assemble(name);
+        let disassembler = DisassemblerClass::new(&assembler.to_hack());
+        let source = disassembler.disassemble();
+        assert!(!source.contains('('), "{name}: no labels without symbols");
+        assert_eq!(AssemblerClass::new(&source).rom, assembler.rom, "{name}");
+    }
+}
+
+#[test]
+fn round_trips_with_symbols() {
+    for name in ["NestedCall", "StaticsTest"] {
+        let assembler = assemble(name);
+        let mut disassembler = DisassemblerClass::new(&assembler.to_hack());
+        disassembler.load_symbols(&assembler.to_symbols());
+        let source = disassembler.disassemble();
+
+        assert_eq!(AssemblerClass::new(&source).rom, assembler.rom, "{name}");
+        assert!(source.contains("(Sys.init)\n"));
+        assert!(source.contains("@Sys.init\n0;JMP\n"));
+        assert!(source.contains("@SP\nM=M+1\n"));
+    }
+
+    let mut disassembler = DisassemblerClass::new(&assemble("StaticsTest").to_hack());
+    disassembler.load_symbols(&assemble("StaticsTest").to_symbols());
+    let source = disassembler.disassemble();
+    assert!(source.contains("@Class2.1\nM=D\n"));
+    assert!(source.contains("@Class1.get.ret."));
+}
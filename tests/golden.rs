@@ -0,0 +1,102 @@
+mod common;
+
+use common::*;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::Path;
+use virtual_machine_translator::prelude::*;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::parser::*;
+use virtual_machine_translator::utils::program::*;
+
+/// Translates every VM file of `fixture` the way the translator's `main` does.
+///
+/// The bootstrap code is only written for programs that declare `Sys.vm`, the other
+/// fixtures start at the first instruction with the RAM prepared by `setup.ram`.
+fn translate(fixture: &Path) -> String {
+    let files = vm_files(&fixture.to_string_lossy());
+    let name = fixture.file_name().unwrap().to_string_lossy().to_string();
+    let output = scratch_dir(&format!("golden_{name}")).join(format!("{name}.asm"));
+
+    let mut writer = CodeWriterClass::new(output.to_string_lossy().to_string());
+    if files.iter().any(|file| file.ends_with("Sys.vm")) {
+        writer.write_init();
+    }
+    for file in &files {
+        writer.file_name = file_name(file);
+        let mut parser = ParserClass::new(BufReader::new(File::open(file).unwrap()));
+        while parser.has_more_commands() {
+            match parser.command_type {
+                Some(Command::Arithmetic(_)) => writer.write_arithmetic(&parser),
+                Some(Command::PushPop(_)) => writer.write_push_pop(&parser),
+                Some(Command::Branch(_)) => writer.write_branch(&parser),
+                Some(Command::Function(_)) => writer.write_function(&parser),
+                None => continue,
+            }
+        }
+    }
+    drop(writer);
+    fs::read_to_string(output).unwrap()
+}
+
+fn run_fixture(name: &str) {
+    let fixture = fixture_dir(name);
+    let assembler = AssemblerClass::new(&translate(&fixture));
+    assert_fixture_ram(name, assembler.rom);
+}
+
+#[test]
+fn simple_add() {
+    run_fixture("SimpleAdd");
+}
+
+#[test]
+fn stack_test() {
+    run_fixture("StackTest");
+}
+
+#[test]
+fn basic_test() {
+    run_fixture("BasicTest");
+}
+
+#[test]
+fn pointer_test() {
+    run_fixture("PointerTest");
+}
+
+#[test]
+fn static_test() {
+    run_fixture("StaticTest");
+}
+
+#[test]
+fn basic_loop() {
+    run_fixture("BasicLoop");
+}
+
+#[test]
+fn fibonacci_series() {
+    run_fixture("FibonacciSeries");
+}
+
+#[test]
+fn simple_function() {
+    run_fixture("SimpleFunction");
+}
+
+#[test]
+fn nested_call() {
+    run_fixture("NestedCall");
+}
+
+#[test]
+fn fibonacci_element() {
+    run_fixture("FibonacciElement");
+}
+
+#[test]
+fn statics_test() {
+    run_fixture("StaticsTest");
+}
@@ -0,0 +1,226 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::inliner::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::vm_interpreter::*;
+
+const POINT: &str = "function Point.getX 0
+push argument 0
+pop pointer 0
+push this 0
+return
+function Point.abs 1
+push argument 0
+push constant 0
+lt
+if-goto NEG
+push argument 0
+return
+label NEG
+push argument 0
+neg
+return";
+
+const SYS: &str = "function Sys.init 1
+push constant 3000
+pop pointer 0
+push constant 5
+neg
+call Point.abs 1
+pop local 0
+push constant 4000
+call Point.getX 1
+push this 0
+add
+pop static 0
+label END
+goto END";
+
+/// Returns the commands of the `function` command named `name` up to the next `function` command.
+fn function(program: &ProgramClass, name: &str) -> Vec<String> {
+    let start = program
+        .instructions
+        .iter()
+        .position(|instruction| instruction.part(1) == Some(name))
+        .unwrap();
+    let mut commands = vec![program.instructions[start].current_command.clone()];
+    commands.extend(
+        program.instructions[start + 1..]
+            .iter()
+            .take_while(|instruction| instruction.name() != "function")
+            .map(|instruction| instruction.current_command.clone()),
+    );
+    commands
+}
+
+fn point_program(name: &str) -> ProgramClass {
+    let dir = write_program(
+        &format!("inliner_{name}"),
+        &[("Point.vm", POINT), ("Sys.vm", SYS)],
+    );
+    ProgramClass::new(vec![dir])
+}
+
+#[test]
+fn inlines_getters_and_restores_pointers() {
+    let mut inliner = InlinerClass::new(&point_program("getter"), INLINE_THRESHOLD);
+    let program = inliner.inline();
+    assert_eq!(
+        function(&program, "Sys.init"),
+        [
+            "function Sys.init 3",
+            "push constant 3000",
+            "pop pointer 0",
+            "push constant 5",
+            "neg",
+            "call Point.abs 1",
+            "pop local 0",
+            "push constant 4000",
+            // The argument goes to local 1 and the base address of `this` is saved in local 2.
+            "pop local 1",
+            "push pointer 0",
+            "pop local 2",
+            "push local 1",
+            "pop pointer 0",
+            "push this 0",
+            "push local 2",
+            "pop pointer 0",
+            "push this 0",
+            "add",
+            "pop static 0",
+            "label END",
+            "goto END",
+        ]
+    );
+    // Point.abs has 10 commands, more than the threshold.
+    assert_eq!(inliner.sites.get("Point.abs"), None);
+    assert_eq!(inliner.sites.get("Point.getX"), Some(&1));
+    assert!(inliner
+        .report()
+        .ends_with("Total                                   1\n"));
+}
+
+#[test]
+fn renames_labels_and_jumps_to_the_end_on_early_returns() {
+    let mut inliner = InlinerClass::new(&point_program("labels"), 10);
+    let program = inliner.inline();
+    assert_eq!(
+        function(&program, "Sys.init")[..17],
+        [
+            "function Sys.init 3",
+            "push constant 3000",
+            "pop pointer 0",
+            "push constant 5",
+            "neg",
+            "pop local 1",
+            "push constant 0",
+            "pop local 2",
+            "push local 1",
+            "push constant 0",
+            "lt",
+            "if-goto NEG.inline.0",
+            "push local 1",
+            "goto Point.abs.inline.0",
+            "label NEG.inline.0",
+            "push local 1",
+            "neg",
+        ]
+    );
+    assert_eq!(
+        function(&program, "Sys.init")[17],
+        "label Point.abs.inline.0"
+    );
+    assert_eq!(inliner.sites.values().sum::<usize>(), 2);
+}
+
+#[test]
+fn keeps_calls_that_cannot_be_inlined() {
+    let main = "function Main.fact 0
+push argument 0
+call Main.fact 1
+return
+function Main.leaves 0
+push constant 1
+push constant 2
+return
+function Main.outside 0
+goto END
+return
+function Main.missing 0
+push argument 1
+return";
+    let sys = "push constant 1
+call Main.missing 1
+function Sys.init 0
+push constant 3
+call Main.fact 1
+call Main.leaves 0
+call Main.outside 0
+push constant 1
+call Main.missing 1
+label END
+goto END";
+    let dir = write_program("inliner_kept", &[("Main.vm", main), ("Sys.vm", sys)]);
+    let program = ProgramClass::new(vec![dir]);
+    let mut inliner = InlinerClass::new(&program, INLINE_THRESHOLD);
+    let inlined = inliner.inline();
+
+    // Recursion, two values left on the stack, a jump out of the function, a missing
+    // argument and a call outside of any function.
+    assert!(inliner.sites.is_empty());
+    let commands = |program: &ProgramClass| -> Vec<String> {
+        program
+            .instructions
+            .iter()
+            .map(|instruction| instruction.current_command.clone())
+            .collect()
+    };
+    assert_eq!(commands(&inlined), commands(&program));
+}
+
+/// Runs a program on the interpreter until it loops at the end of `Sys.init`.
+fn run(program: &ProgramClass) -> InterpreterClass {
+    let mut interpreter = InterpreterClass::new(program);
+    interpreter.run(200_000);
+    assert_eq!(interpreter.error, None);
+    assert_eq!(interpreter.frame().function, "Sys.init");
+    interpreter
+}
+
+#[test]
+fn inlined_programs_compute_the_same_results() {
+    let mut sites = 0;
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("inliner_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        let mut inliner = InlinerClass::new(&program, 40);
+        let inlined = inliner.inline();
+        sites += inliner.sites.values().sum::<usize>();
+
+        let (expected, actual) = (run(&program), run(&inlined));
+        assert_eq!(actual.statics, expected.statics, "seed {seed}");
+        assert_eq!(actual.heap, expected.heap, "seed {seed}");
+        assert_eq!(actual.temp, expected.temp, "seed {seed}");
+        assert_eq!(actual.pointer, expected.pointer, "seed {seed}");
+
+        // The inlined program also translates correctly.
+        let mut harness = DifferentialClass::from_programs(
+            &inlined,
+            &inlined,
+            &CodegenOptions::default(),
+            format!("{dir}/Out.asm"),
+        );
+        harness
+            .run(200_000)
+            .unwrap_or_else(|error| panic!("seed {seed}: {error}"));
+    }
+    assert!(sites > 10);
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use common::*;
+use serde_json::{json, Value};
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::json_export::*;
+use virtual_machine_translator::utils::linter::*;
+use virtual_machine_translator::utils::program::*;
+
+/// Translates the program with the bootstrap code and parses its JSON export.
+fn export(name: &str, program: &ProgramClass) -> Value {
+    let output = scratch_dir(&format!("json_export_{name}")).join("Out.asm");
+    let mut writer = CodeWriterClass::new(output.to_string_lossy().to_string());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    let diagnostics = LinterClass::new(program).check();
+    let export = JsonExportClass::new(program, &writer, diagnostics, "Sys.init");
+    serde_json::from_str(&export.to_json()).unwrap()
+}
+
+#[test]
+fn exports_the_program_and_its_analyses() {
+    let fixture = format!("{}/tests/fixtures/NestedCall", env!("CARGO_MANIFEST_DIR"));
+    let program = ProgramClass::new(vec![fixture]);
+    let json = export("nested_call", &program);
+
+    assert_eq!(json["entry"], "Sys.init");
+    let instructions = json["instructions"].as_array().unwrap();
+    assert_eq!(instructions.len(), program.instructions.len());
+    assert_eq!(
+        instructions[1],
+        json!({
+            "file_name": "Sys.vm",
+            "line": 3,
+            "current_command": "push constant 4000",
+            "command_type": { "PushPop": "push" },
+            "segment_type": { "External": "constant" },
+            "index": 4000,
+            "allow": [],
+        })
+    );
+    assert_eq!(
+        json["diagnostics"][0],
+        json!({
+            "severity": "warning",
+            "file_name": "Sys.vm",
+            "line": 41,
+            "message": "the base address of this set here is never used",
+            "rule": "unused-pointer",
+        })
+    );
+    assert_eq!(
+        json["call_graph"]["calls"],
+        json!({ "Sys.add12": [], "Sys.init": ["Sys.main"], "Sys.main": ["Sys.add12"] })
+    );
+    assert_eq!(json["call_graph"]["functions"][1]["name"], "Sys.main");
+    assert_eq!(json["statistics"]["rom_usage"]["total"], 613);
+    assert_eq!(json["statistics"]["stack_usage"]["usage"]["Sys.main"], 13);
+    assert_eq!(json["statistics"]["stack_bound"], 23);
+    assert_eq!(
+        json["source_map"][0],
+        json!({
+            "address": 0,
+            "length": 54,
+            "file_name": "bootstrap",
+            "line": 0,
+            "command": "bootstrap",
+            "function": "",
+        })
+    );
+}
+
+#[test]
+fn exports_unbounded_stacks_and_statics() {
+    let fixture = format!(
+        "{}/tests/fixtures/FibonacciElement",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let json = export("fibonacci", &ProgramClass::new(vec![fixture]));
+    assert_eq!(json["statistics"]["stack_bound"], Value::Null);
+    assert_eq!(
+        json["statistics"]["stack_usage"]["cycles"],
+        json!([["Main.fibonacci"]])
+    );
+
+    let fixture = format!("{}/tests/fixtures/StaticsTest", env!("CARGO_MANIFEST_DIR"));
+    let json = export("statics", &ProgramClass::new(vec![fixture]));
+    let statics = &json["statistics"]["statics"];
+    assert_eq!(statics["statics"][0]["address"], 16);
+    assert_eq!(statics["modules"], json!({ "Class1": 2, "Class2": 2 }));
+}
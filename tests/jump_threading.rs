@@ -0,0 +1,208 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::prelude::*;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::assembly::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::jump_threading::*;
+use virtual_machine_translator::utils::pass_manager::*;
+use virtual_machine_translator::utils::program::*;
+
+/// Builds an assembly of one block per entry of `blocks`, named after its first line.
+fn assembly(blocks: &[&str]) -> AssemblyClass {
+    AssemblyClass {
+        blocks: blocks
+            .iter()
+            .map(|code| AssemblyBlock {
+                entry: SourceMapEntry {
+                    command: code.lines().next().unwrap_or_default().to_string(),
+                    ..Default::default()
+                },
+                lines: code.lines().map(str::to_string).collect(),
+            })
+            .collect(),
+    }
+}
+
+/// Runs the pass and returns it and the lines of every block.
+fn thread(blocks: &[&str]) -> (JumpThreadingClass, Vec<String>) {
+    let mut pass = JumpThreadingClass::new(&assembly(blocks));
+    let threaded = pass.thread();
+    let lines = threaded
+        .blocks
+        .iter()
+        .map(|block| block.lines.join(" "))
+        .collect();
+    (pass, lines)
+}
+
+#[test]
+fn threads_jump_chains() {
+    let (pass, lines) = thread(&[
+        "// goto A\n@A\n0;JMP",
+        "// push constant 1\n@1\nD=A",
+        "// label A\n(A)",
+        "// label B\n(B)",
+        "// goto C\n@C\n0;JMP",
+        "// label C\n(C)\n@7\nD=A",
+        "// goto B\n@B\n0;JMP",
+        "// if-goto A\n@SP\nAM=M-1\nD=M\n@A\nD;JNE",
+    ]);
+    assert_eq!(
+        lines,
+        [
+            "// goto A @C 0;JMP",
+            "// push constant 1 @1 D=A",
+            "// label A (A)",
+            "// label B (B)",
+            "// goto C",
+            "// label C (C) @7 D=A",
+            "// goto B @C 0;JMP",
+            "// if-goto A @SP AM=M-1 D=M @C D;JNE"
+        ]
+    );
+    // The jump of `goto C` to the next instruction goes, then the three others are threaded.
+    assert_eq!((pass.threaded, pass.removed, pass.inverted), (3, 1, 0));
+}
+
+#[test]
+fn inverts_conditional_over_unconditional_jumps() {
+    let (pass, lines) = thread(&[
+        "// gt; if-goto TRUE (fused)\n@SP\nAM=M-1\nD=M\n@SP\nAM=M-1\nD=M-D\n@TRUE\nD;JGT",
+        "// goto FALSE\n@FALSE\n0;JMP",
+        "// label TRUE\n(TRUE)\n@1\nD=A",
+        "// label FALSE\n(FALSE)\n@2\nD=A",
+    ]);
+    assert_eq!(
+        lines,
+        [
+            "// gt; if-goto TRUE (fused) @SP AM=M-1 D=M @SP AM=M-1 D=M-D @FALSE D;JLE",
+            "// goto FALSE",
+            "// label TRUE (TRUE) @1 D=A",
+            "// label FALSE (FALSE) @2 D=A"
+        ]
+    );
+    assert_eq!((pass.threaded, pass.removed, pass.inverted), (0, 0, 1));
+}
+
+#[test]
+fn leaves_other_jumps_alone() {
+    let blocks = [
+        // Halting loops, indirect jumps and jumps reading or writing memory.
+        "// label END\n(END)\n@END\n0;JMP",
+        "// return\n@14\nA=M\n0;JMP",
+        "// odd\n@NEXT\nM;JNE\n@NEXT\nAM=M-1;JMP",
+        "// label NEXT\n(NEXT)",
+        // A jump with a label between it and the jump over.
+        "// if-goto X\n@X\nD;JEQ",
+        "// label Y\n(Y)\n@Z\n0;JMP",
+        "// label X\n(X)\n@3\nD=A",
+        "// label Z\n(Z)",
+    ];
+    let (pass, lines) = thread(&blocks);
+    let unchanged: Vec<String> = blocks.iter().map(|code| code.replace('\n', " ")).collect();
+    assert_eq!(lines, unchanged);
+    assert_eq!((pass.threaded, pass.removed, pass.inverted), (0, 0, 0));
+}
+
+#[test]
+fn source_map_follows_the_removed_jumps() {
+    let mut pass = JumpThreadingClass::new(&assembly(&[
+        "// goto NEXT\n@NEXT\n0;JMP",
+        "// label NEXT\n(NEXT)",
+        "// push constant 1\n@1\nD=A",
+    ]));
+    let source_map = pass.thread().source_map();
+    let spans: Vec<(usize, usize)> = source_map
+        .iter()
+        .map(|entry| (entry.address, entry.length))
+        .collect();
+    assert_eq!(spans, [(0, 0), (0, 0), (0, 2)]);
+}
+
+/// Translates a program without bootstrap code through `manager` and runs it until it halts.
+fn run(dir: &str, output: &str, manager: &mut PassManagerClass) -> (usize, Vec<i16>) {
+    let program = ProgramClass::new(vec![dir.to_string()]);
+    let output = format!("{dir}/{output}");
+    let init = program
+        .functions()
+        .iter()
+        .any(|function| function.name == "Sys.init");
+    manager.translate(&program, output.clone(), init);
+    let rom = AssemblerClass::new(&fs::read_to_string(output).unwrap()).rom;
+    let length = rom.len();
+    let mut emulator = EmulatorClass::new(rom);
+    emulator.ram[0] = 256;
+    emulator.run(2_000_000);
+    assert!(emulator.is_halted(), "{dir} did not halt");
+    (length, emulator.ram[..4096].to_vec())
+}
+
+#[test]
+fn branches_over_gotos_get_shorter() {
+    let dir = write_program(
+        "jump_threading_branches",
+        &[(
+            "Main.vm",
+            "push constant 5
+pop temp 0
+label LOOP
+push temp 0
+if-goto BODY
+goto DONE
+label BODY
+push temp 0
+push constant 1
+sub
+pop temp 0
+goto NEXT
+label NEXT
+goto LOOP
+label DONE
+push constant 9
+pop temp 1
+",
+        )],
+    );
+    let (plain_length, plain) = run(&dir, "Plain.asm", &mut PassManagerClass::new(OptLevel::O0));
+    let mut manager = PassManagerClass::new(OptLevel::O0);
+    manager.add_assembly_pass(Box::<JumpThreadingClass>::default());
+    let (length, threaded) = run(&dir, "Threaded.asm", &mut manager);
+    assert_eq!(threaded, plain);
+    assert_eq!((threaded[5], threaded[6]), (0, 9));
+    // Inverting `if-goto BODY; goto DONE` and removing `goto NEXT` save two jumps of two words.
+    assert_eq!(length, plain_length - 4);
+}
+
+#[test]
+fn random_programs_keep_their_results() {
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("jump_threading_random_{seed}"), &files);
+        for codegen in [
+            CodegenOptions::default(),
+            CodegenOptions {
+                specialise: true,
+                cache_top: true,
+                ..Default::default()
+            },
+        ] {
+            let mut plain = PassManagerClass::new(OptLevel::O0);
+            plain.codegen = codegen.clone();
+            let mut threading = PassManagerClass::new(OptLevel::O0);
+            threading.codegen = codegen;
+            threading.add_assembly_pass(Box::<JumpThreadingClass>::default());
+            let (plain_length, expected) = run(&dir, "Plain.asm", &mut plain);
+            let (length, actual) = run(&dir, "Threaded.asm", &mut threading);
+            assert_eq!(actual, expected, "seed {seed}");
+            assert!(length <= plain_length, "seed {seed}");
+        }
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::label_checker::*;
+use virtual_machine_translator::utils::program::*;
+
+fn check(name: &str, files: &[(&str, &str)]) -> Vec<String> {
+    let program = ProgramClass::new(vec![write_program(name, files)]);
+    LabelCheckerClass::new(&program)
+        .check()
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+}
+
+#[test]
+fn fixtures_and_random_programs_are_consistent() {
+    for name in [
+        "BasicLoop",
+        "FibonacciSeries",
+        "FibonacciElement",
+        "NestedCall",
+    ] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let program = ProgramClass::new(vec![fixture]);
+        assert!(
+            LabelCheckerClass::new(&program).check().is_empty(),
+            "{name}"
+        );
+    }
+    for seed in 0..10 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        assert!(check(&format!("labels_random_{seed}"), &files).is_empty());
+    }
+}
+
+#[test]
+fn reports_undeclared_and_foreign_labels() {
+    let main = "function Main.f 0
+label LOOP
+goto LOOP
+goto DONE
+function Main.g 0
+if-goto LOOP
+push constant 0
+return";
+    assert_eq!(
+        check("labels_undeclared", &[("Main.vm", main)]),
+        [
+            "Main.vm:4: error: label DONE is not declared in Main.f",
+            "Main.vm:6: error: if-goto LOOP jumps into Main.f, labels can only be used in the function declaring them",
+        ]
+    );
+}
+
+#[test]
+fn reports_duplicate_labels() {
+    let main = "function Main.f 0
+label LOOP
+label LOOP
+goto LOOP
+function Main.g 0
+label LOOP
+goto LOOP";
+    assert_eq!(
+        check("labels_duplicate", &[("Main.vm", main)]),
+        // Labels are scoped by the translator, so Main.g may declare LOOP too.
+        ["Main.vm:3: error: label LOOP is already declared at Main.vm:2"]
+    );
+}
+
+#[test]
+fn reports_clashes_with_generated_symbols() {
+    let main = "label CON_TRUE_0
+label CON_FINISH_12
+label while
+label returnAddress.0
+label Main.g.ret.3
+label Main.g
+label SP
+label R13
+label CON_TRUE_
+label Main.g.ret.x
+function Main.g 0
+label CON_TRUE_0
+label SP
+push constant 0
+return";
+    assert_eq!(
+        check("labels_clash", &[("Main.vm", main)]),
+        [
+            "Main.vm:1: error: label CON_TRUE_0 clashes with the labels generated for comparisons",
+            "Main.vm:2: error: label CON_FINISH_12 clashes with the labels generated for comparisons",
+            "Main.vm:3: error: label while clashes with the labels of the bootstrap code",
+            "Main.vm:4: error: label returnAddress.0 clashes with the labels of the bootstrap code",
+            "Main.vm:5: error: label Main.g.ret.3 clashes with the return addresses generated for calls",
+            "Main.vm:6: error: label Main.g clashes with the entry point of function Main.g",
+            "Main.vm:7: error: label SP clashes with the predefined symbol SP",
+            "Main.vm:8: error: label R13 clashes with the predefined symbol R13",
+        ]
+    );
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::linter::*;
+use virtual_machine_translator::utils::program::*;
+
+fn lint(name: &str, source: &str, disabled: &[&str]) -> Vec<String> {
+    let program = ProgramClass::new(vec![write_program(name, &[("Main.vm", source)])]);
+    let mut linter = LinterClass::new(&program);
+    for rule in disabled {
+        linter.set_rule(rule, false).unwrap();
+    }
+    linter
+        .check()
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+}
+
+const SOURCE: &str = "function Main.f 1
+push local 0
+pop local 0
+push argument 0
+if-goto ELSE
+push constant 1
+return
+push constant 2
+label ELSE
+label UNUSED
+push constant 3
+pop pointer 0
+push constant 4
+pop pointer 0
+push this 0
+pop pointer 1
+return
+function Main.g 0
+push constant 0
+if-goto END
+label END";
+
+#[test]
+fn reports_every_rule() {
+    assert_eq!(
+        lint("lint_all", SOURCE, &[]),
+        [
+            "Main.vm:2: warning: `push local 0` followed by `pop local 0` has no effect [push-pop-noop]",
+            "Main.vm:8: warning: unreachable code [unreachable-code]",
+            "Main.vm:10: warning: label UNUSED is never used [unused-label]",
+            "Main.vm:12: warning: the base address of this set here is never used [unused-pointer]",
+            "Main.vm:16: warning: the base address of that set here is never used [unused-pointer]",
+            "Main.vm:21: warning: Main.g can run past its end without `return` [missing-return]",
+        ]
+    );
+}
+
+#[test]
+fn rules_can_be_disabled() {
+    let warnings = lint(
+        "lint_disabled",
+        SOURCE,
+        &["unused-pointer", "push-pop-noop", "unused-label"],
+    );
+    assert_eq!(warnings.len(), 2);
+    assert!(lint("lint_none", SOURCE, &["all"]).is_empty());
+
+    let program = ProgramClass::new(vec![write_program("lint_unknown", &[("Main.vm", SOURCE)])]);
+    let mut linter = LinterClass::new(&program);
+    assert!(linter.set_rule("unused-variable", false).is_err());
+    linter.set_rule("all", false).unwrap();
+    linter.set_rule("missing-return", true).unwrap();
+    assert_eq!(linter.check().len(), 1);
+}
+
+#[test]
+fn rules_can_be_allowed_per_line() {
+    let source = SOURCE
+        .replace(
+            "push constant 2",
+            "push constant 2 // vm-allow(unreachable-code)",
+        )
+        .replace(
+            "pop pointer 1",
+            "pop pointer 1 // Reset. vm-allow(unused-label, unused-pointer)",
+        )
+        .replace("label END", "label END // vm-allow(missing-return)");
+    let warnings = lint("lint_allowed", &source, &["push-pop-noop"]);
+    assert_eq!(
+        warnings,
+        [
+            "Main.vm:10: warning: label UNUSED is never used [unused-label]",
+            "Main.vm:12: warning: the base address of this set here is never used [unused-pointer]",
+        ]
+    );
+}
+
+#[test]
+fn fixtures_are_clean() {
+    for name in [
+        "BasicLoop",
+        "FibonacciSeries",
+        "FibonacciElement",
+        "StaticsTest",
+    ] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let linter = LinterClass::new(&ProgramClass::new(vec![fixture]));
+        assert!(linter.check().is_empty(), "{name}: {:?}", linter.check());
+    }
+}
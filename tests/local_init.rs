@@ -0,0 +1,132 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::program::*;
+
+const LEVELS: [OptLevel; 4] = [OptLevel::O0, OptLevel::O1, OptLevel::O2, OptLevel::Os];
+
+/// The value left in the RAM before the program runs, which the locals must not keep.
+const GARBAGE: i16 = 0x1234;
+
+/// Writes a program where `Sys.init` calls `Main.f`, which declares `vars` locals and stops.
+fn program(name: &str, vars: usize) -> ProgramClass {
+    let dir = write_program(
+        name,
+        &[
+            (
+                "Sys.vm",
+                "function Sys.init 0\npush constant 5\ncall Main.f 0\nlabel HALT\ngoto HALT\n",
+            ),
+            (
+                "Main.vm",
+                &format!("function Main.f {vars}\nlabel STOP\ngoto STOP\n"),
+            ),
+        ],
+    );
+    ProgramClass::new(vec![dir])
+}
+
+/// Translates `program` with the code generation of `level` only and returns the writer and the ROM.
+fn translate(name: &str, program: &ProgramClass, level: OptLevel) -> (CodeWriterClass, Vec<u16>) {
+    let output = scratch_dir(name).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.options = CodegenOptions {
+        level,
+        ..Default::default()
+    };
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    writer.flush();
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    (writer, assembler.rom)
+}
+
+/// Returns the ROM words written for `function Main.f`.
+fn function_length(writer: &CodeWriterClass) -> usize {
+    writer
+        .source_map
+        .iter()
+        .find(|entry| entry.command.starts_with("function Main.f"))
+        .unwrap()
+        .length
+}
+
+#[test]
+fn large_frames_take_fewer_words() {
+    let words = |vars: usize, level: OptLevel| {
+        let name = format!("local_init_size_{vars}_{level:?}");
+        function_length(&translate(&name, &program(&name, vars), level).0)
+    };
+    // One template of 7 words per local at O0, 2 per local and 5 to move SP unrolled, 12 for the loop.
+    assert_eq!(words(30, OptLevel::O0), 210);
+    assert_eq!(words(30, OptLevel::O1), 12);
+    assert_eq!(words(30, OptLevel::O2), 65);
+    assert_eq!(words(30, OptLevel::Os), 12);
+    assert_eq!(words(200, OptLevel::O2), 405);
+    assert_eq!(words(3, OptLevel::Os), 11);
+    assert_eq!(words(9, OptLevel::O1), 23);
+    assert_eq!(words(10, OptLevel::O1), 12);
+    for level in LEVELS {
+        assert_eq!(words(0, level), 0, "{level:?}");
+    }
+}
+
+#[test]
+fn locals_are_zero_and_sp_is_past_them() {
+    for level in LEVELS {
+        for vars in [0, 1, 2, 3, 4, 8, 9, 10, 20, 60] {
+            let name = format!("local_init_run_{vars}_{level:?}");
+            let (_, rom) = translate(&name, &program(&name, vars), level);
+            let mut emulator = EmulatorClass::new(rom);
+            for address in 256..1024 {
+                emulator.ram[address] = GARBAGE;
+            }
+            emulator.run(100_000);
+            assert!(emulator.is_halted(), "{level:?} {vars}: did not halt");
+
+            let lcl = emulator.ram[1] as usize;
+            let sp = emulator.ram[0] as usize;
+            assert_eq!(sp, lcl + vars, "{level:?} {vars}: SP");
+            for address in lcl..sp {
+                assert_eq!(emulator.ram[address], 0, "{level:?} {vars}: RAM[{address}]");
+            }
+            assert_eq!(emulator.ram[sp], GARBAGE, "{level:?} {vars}: past SP");
+        }
+    }
+}
+
+#[test]
+fn random_programs_match_interpreter() {
+    for seed in 0..20 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("local_init_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        for level in LEVELS {
+            let options = CodegenOptions {
+                level,
+                ..Default::default()
+            };
+            let mut harness = DifferentialClass::from_programs(
+                &program,
+                &program,
+                &options,
+                format!("{dir}/{level:?}.asm"),
+            );
+            harness
+                .run(200_000)
+                .unwrap_or_else(|error| panic!("seed {seed} {level:?}: {error}"));
+        }
+    }
+}
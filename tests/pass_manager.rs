@@ -0,0 +1,290 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::assembly::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::pass_manager::*;
+use virtual_machine_translator::utils::program::*;
+
+const LEVELS: [OptLevel; 4] = [OptLevel::O0, OptLevel::O1, OptLevel::O2, OptLevel::Os];
+
+/// Loads a fixture.
+fn load(name: &str) -> ProgramClass {
+    let fixture = fixture_dir(name);
+    ProgramClass::new(vec![fixture.to_string_lossy().to_string()])
+}
+
+/// Translates a fixture through `manager` like the golden tests, the bootstrap code only for
+/// programs with `Sys.vm`, and returns the writer, the assembled output and the optimised program.
+fn translate(
+    name: &str,
+    manager: &mut PassManagerClass,
+) -> (CodeWriterClass, AssemblerClass, ProgramClass) {
+    let fixture = fixture_dir(name);
+    let output = scratch_dir(&format!("pass_manager_{name}_{:?}", manager.level)).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let program = manager.optimise(&load(name));
+    let writer = manager.translate(&program, output.clone(), fixture.join("Sys.vm").exists());
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    (writer, assembler, program)
+}
+
+/// Checks that the fixture translated through `manager` leaves the expected RAM.
+///
+/// Inlining gives the callers more local variables, moving the frames of the functions still
+/// running. The pointers, temp and the statics are then checked as they are, and the working
+/// stack of the running function, relative to SP, against the `-O0` translation.
+fn check_fixture(name: &str, manager: &mut PassManagerClass) {
+    let (writer, assembler, optimised) = translate(name, manager);
+    let declarations = |program: &ProgramClass| -> Vec<usize> {
+        program
+            .functions()
+            .iter()
+            .map(|function| function.n_vars)
+            .collect()
+    };
+    if declarations(&optimised) == declarations(&load(name)) {
+        assert_fixture_ram(name, assembler.rom);
+        return;
+    }
+    let (plain_writer, plain, program) = translate(name, &mut PassManagerClass::new(OptLevel::O0));
+    let reference = assert_fixture_ram(name, plain.rom);
+    let emulator =
+        assert_fixture_ram_where(name, assembler.rom, |address| (1..256).contains(&address));
+    assert_eq!(
+        working_stack(&writer, &optimised, &emulator),
+        working_stack(&plain_writer, &program, &reference),
+        "{name}: working stack"
+    );
+}
+
+/// Returns the words the running function pushed above its local variables, bottom first.
+fn working_stack(
+    writer: &CodeWriterClass,
+    program: &ProgramClass,
+    emulator: &EmulatorClass,
+) -> Vec<i16> {
+    let entry = writer
+        .source_map
+        .iter()
+        .rev()
+        .find(|entry| entry.address <= emulator.pc)
+        .unwrap();
+    let function = program
+        .functions()
+        .into_iter()
+        .find(|function| function.name == entry.function)
+        .unwrap();
+    let base = emulator.ram[1] as usize + function.n_vars;
+    emulator.ram[base..emulator.ram[0] as usize].to_vec()
+}
+
+/// Drops the comment lines of the generated assembly.
+struct StripComments;
+
+impl AssemblyPass for StripComments {
+    fn name(&self) -> String {
+        "strip-comments".to_string()
+    }
+
+    fn run(&mut self, assembly: &AssemblyClass) -> AssemblyClass {
+        let mut assembly = assembly.clone();
+        for block in &mut assembly.blocks {
+            block
+                .lines
+                .retain(|line| !line.trim_start().starts_with("//"));
+        }
+        assembly
+    }
+}
+
+/// Appends `push constant 0; pop temp 7` to the program.
+struct ClearTemp;
+
+impl VmPass for ClearTemp {
+    fn name(&self) -> String {
+        "clear-temp".to_string()
+    }
+
+    fn run(&mut self, program: &ProgramClass) -> ProgramClass {
+        let dir = write_program(
+            "pass_manager_clear_temp",
+            &[("Temp.vm", "push constant 0\npop temp 7\n")],
+        );
+        let mut program = program.clone();
+        program
+            .instructions
+            .extend(ProgramClass::new(vec![dir]).instructions);
+        program
+    }
+}
+
+#[test]
+fn levels_map_to_pipelines() {
+    let pipeline = |level: OptLevel| {
+        let manager = PassManagerClass::new(level);
+        let names: Vec<String> = manager.vm_passes.iter().map(|pass| pass.name()).collect();
+        (names, manager.codegen.specialise, manager.codegen.cache_top)
+    };
+    let names =
+        |names: &[&str]| -> Vec<String> { names.iter().map(|name| name.to_string()).collect() };
+    assert_eq!(pipeline(OptLevel::O0), (vec![], false, false));
+    assert_eq!(
+        pipeline(OptLevel::O1),
+        (names(&["fold-constants", "dead-code"]), true, false)
+    );
+    assert_eq!(
+        pipeline(OptLevel::O2),
+        (
+            names(&["inline", "fold-constants", "dead-code"]),
+            true,
+            true
+        )
+    );
+    assert_eq!(
+        pipeline(OptLevel::Os),
+        (names(&["fold-constants", "dead-code"]), true, true)
+    );
+    for level in LEVELS {
+        let manager = PassManagerClass::new(level);
+        let names: Vec<String> = manager
+            .assembly_passes
+            .iter()
+            .map(|pass| pass.name())
+            .collect();
+        let expected: &[&str] = match level {
+            OptLevel::O0 => &[],
+            _ => &["thread-jumps"],
+        };
+        assert_eq!(names, expected, "{level:?}");
+    }
+    assert_eq!(OptLevel::parse("-Os"), Some(OptLevel::Os));
+    assert_eq!(OptLevel::parse("-O3"), None);
+    assert_eq!(OptLevel::default(), OptLevel::O0);
+}
+
+#[test]
+fn o0_matches_the_code_writer() {
+    for name in FIXTURES {
+        let (writer, _, _) = translate(name, &mut PassManagerClass::new(OptLevel::O0));
+        let fixture = fixture_dir(name);
+        let output = scratch_dir(&format!("pass_manager_plain_{name}")).join("Out.asm");
+        let mut plain = CodeWriterClass::new(output.to_string_lossy().to_string());
+        if fixture.join("Sys.vm").exists() {
+            plain.write_init();
+        }
+        for instruction in &load(name).instructions {
+            plain.write_instruction(instruction);
+        }
+        plain.flush();
+        assert_eq!(writer.source_map, plain.source_map, "{name}");
+        assert_eq!(writer.code, plain.code, "{name}");
+    }
+}
+
+#[test]
+fn every_level_gives_the_expected_ram() {
+    for level in LEVELS {
+        for name in FIXTURES {
+            check_fixture(name, &mut PassManagerClass::new(level));
+        }
+    }
+}
+
+#[test]
+fn optimised_levels_shrink_the_output() {
+    for level in [OptLevel::O1, OptLevel::Os] {
+        let (mut plain, mut optimised) = (0, 0);
+        for name in FIXTURES {
+            plain += translate(name, &mut PassManagerClass::new(OptLevel::O0))
+                .1
+                .rom
+                .len();
+            optimised += translate(name, &mut PassManagerClass::new(level))
+                .1
+                .rom
+                .len();
+        }
+        assert!(
+            optimised * 10 < plain * 9,
+            "{level:?}: {optimised} of {plain}"
+        );
+    }
+}
+
+#[test]
+fn registered_passes_run_in_order_and_are_dumped() {
+    let mut manager = PassManagerClass::new(OptLevel::O1);
+    manager.add_vm_pass(Box::new(ClearTemp));
+    manager.add_assembly_pass(Box::new(StripComments));
+    manager.dump = true;
+    let (writer, assembler, _) = translate("BasicTest", &mut manager);
+
+    let dumps: Vec<String> = manager
+        .dumps
+        .iter()
+        .map(|dump| format!("{}.{}", dump.pass, dump.stage))
+        .collect();
+    assert_eq!(
+        dumps,
+        [
+            "input.vm",
+            "fold-constants.vm",
+            "dead-code.vm",
+            "clear-temp.vm",
+            "codegen.asm",
+            "thread-jumps.asm",
+            "strip-comments.asm"
+        ]
+    );
+    assert!(manager.dumps[3]
+        .code
+        .ends_with("push constant 0\npop temp 7\n"));
+    assert!(manager.dumps[4].code.contains("// "));
+    assert!(!manager.dumps[6].code.contains("//"));
+
+    // The writer describes the assembly of the last pass, which is the output file.
+    let asm = &manager.dumps[6].code;
+    assert_eq!(AssemblerClass::new(asm).rom, assembler.rom);
+    let last = writer.source_map.last().unwrap();
+    assert_eq!(last.command, "pop temp 7");
+    assert_eq!(last.address + last.length, assembler.rom.len());
+    assert_eq!(writer.rom_usage.total, assembler.rom.len());
+
+    let mut emulator = EmulatorClass::new(assembler.rom);
+    emulator.ram[0] = 256;
+    emulator.ram[12] = 55;
+    emulator.run(10_000);
+    assert_eq!(emulator.ram[12], 0);
+}
+
+#[test]
+fn random_programs_match_interpreter() {
+    for seed in 0..40 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("pass_manager_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        for level in LEVELS {
+            let mut manager = PassManagerClass::new(level);
+            let optimised = manager.optimise(&program);
+            // Inlining removes returns, the harness compares the translation of the optimised program.
+            let mut harness = DifferentialClass::from_programs(
+                &optimised,
+                &optimised,
+                &manager.codegen,
+                format!("{dir}/{level:?}.asm"),
+            );
+            harness
+                .run(200_000)
+                .unwrap_or_else(|error| panic!("seed {seed} {level:?}: {error}"));
+        }
+    }
+}
@@ -0,0 +1,99 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::prelude::TOP_LEVEL;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::profiler::*;
+use virtual_machine_translator::utils::program::*;
+
+fn profile(name: &str) -> (ProfilerClass, usize) {
+    let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    profile_dir(name, fixture)
+}
+
+/// Translates the program in `dir` with the bootstrap code and profiles it until it halts.
+fn profile_dir(name: &str, dir: String) -> (ProfilerClass, usize) {
+    let output = scratch_dir(&format!("profiler_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+
+    let program = ProgramClass::new(vec![dir]);
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    let mut profiler = ProfilerClass::new(EmulatorClass::new(assembler.rom), writer.source_map);
+    let cycles = profiler.run(1_000_000);
+    assert!(profiler.emulator.is_halted(), "{name}");
+    (profiler, cycles)
+}
+
+#[test]
+fn every_cycle_is_charged_once() {
+    for name in ["FibonacciElement", "NestedCall", "StaticsTest"] {
+        let (profiler, cycles) = profile(name);
+
+        let self_cycles: usize = profiler.functions.values().map(|f| f.self_cycles).sum();
+        let opcode_cycles: usize = profiler.opcodes.values().map(|o| o.cycles).sum();
+        let folded: usize = profiler
+            .folded()
+            .lines()
+            .map(|line| line.rsplit_once(' ').unwrap().1.parse::<usize>().unwrap())
+            .sum();
+        assert_eq!(self_cycles, cycles, "{name}");
+        assert_eq!(opcode_cycles, cycles, "{name}");
+        assert_eq!(folded, cycles, "{name}");
+        assert_eq!(profiler.functions[TOP_LEVEL].inclusive_cycles, cycles);
+        assert_eq!(profiler.functions["Sys.init"].calls, 1);
+    }
+}
+
+#[test]
+fn recursive_calls_are_counted() {
+    let (profiler, cycles) = profile("FibonacciElement");
+    let fibonacci = &profiler.functions["Main.fibonacci"];
+
+    // fibonacci(4) calls itself for 3, 2, 1, 0, 2, 1, 0 and 1.
+    assert_eq!(fibonacci.calls, 9);
+    assert_eq!(fibonacci.self_cycles, fibonacci.inclusive_cycles);
+    assert!(fibonacci.inclusive_cycles < cycles);
+    assert_eq!(profiler.opcodes["call"].count, 9);
+    assert_eq!(profiler.opcodes["return"].count, 9);
+    assert!(profiler
+        .folded()
+        .contains("<top level>;Sys.init;Main.fibonacci;Main.fibonacci "));
+}
+
+#[test]
+fn nested_calls_are_inclusive() {
+    let (profiler, _) = profile("NestedCall");
+    let main = &profiler.functions["Sys.main"];
+    let add12 = &profiler.functions["Sys.add12"];
+
+    assert_eq!(add12.calls, 1);
+    assert_eq!(
+        main.inclusive_cycles,
+        main.self_cycles + add12.inclusive_cycles
+    );
+    assert!(profiler.report().starts_with("Function"));
+}
+
+#[test]
+fn commands_looping_on_themselves_are_counted_every_time() {
+    let sys = "function Sys.init 0
+push constant 0
+push constant 1
+push constant 1
+label X
+if-goto X
+label END
+goto END";
+    let dir = write_program("profiler_self_loop_program", &[("Sys.vm", sys)]);
+    let (profiler, _) = profile_dir("self_loop", dir);
+    // The if-goto jumps back to itself twice, then falls through on the 0.
+    assert_eq!(profiler.opcodes["if-goto"].count, 3);
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::prelude::TOP_LEVEL;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::rom_usage::*;
+
+/// Translates the program with the bootstrap code and returns the writer and the assembled ROM size.
+fn translate(name: &str, program: &ProgramClass) -> (CodeWriterClass, usize) {
+    let output = scratch_dir(&format!("rom_usage_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    (writer, assembler.rom.len())
+}
+
+#[test]
+fn counts_every_assembled_instruction() {
+    let mut programs: Vec<(String, ProgramClass)> = Vec::new();
+    for name in ["NestedCall", "FibonacciElement", "StaticsTest"] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        programs.push((name.to_string(), ProgramClass::new(vec![fixture])));
+    }
+    for seed in 0..10 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let name = format!("random_{seed}");
+        let dir = write_program(&format!("rom_usage_{name}"), &files);
+        programs.push((name, ProgramClass::new(vec![dir])));
+    }
+
+    for (name, program) in programs {
+        let (writer, rom) = translate(&name, &program);
+        let usage = &writer.rom_usage;
+        assert_eq!(usage.total, rom, "{name}");
+        for counts in [&usage.opcodes, &usage.functions, &usage.files] {
+            assert_eq!(counts.values().sum::<usize>(), rom, "{name}");
+        }
+        assert!(usage.check(ROM_SIZE, &writer.source_map).is_empty());
+    }
+}
+
+#[test]
+fn counts_per_opcode_function_and_file() {
+    let fixture = format!("{}/tests/fixtures/NestedCall", env!("CARGO_MANIFEST_DIR"));
+    let (writer, _) = translate("nested_call", &ProgramClass::new(vec![fixture]));
+    let usage = &writer.rom_usage;
+
+    assert_eq!(usage.total, 613);
+    assert_eq!(usage.opcodes["bootstrap"], 54);
+    assert_eq!(usage.opcodes["label"], 0);
+    assert_eq!(usage.opcodes["return"], 116);
+    assert_eq!(usage.functions[TOP_LEVEL], 54);
+    assert_eq!(usage.functions["Sys.add12"], 116);
+    assert_eq!(usage.files["bootstrap"], 54);
+    assert_eq!(usage.files["Sys.vm"], 559);
+    assert!(usage
+        .report(ROM_SIZE)
+        .ends_with("\nROM usage: 613 of 32768 words (1.87%)\n"));
+}
+
+#[test]
+fn fails_past_the_limit() {
+    let fixture = format!("{}/tests/fixtures/NestedCall", env!("CARGO_MANIFEST_DIR"));
+    let (writer, _) = translate("limit", &ProgramClass::new(vec![fixture]));
+    let usage = &writer.rom_usage;
+
+    assert!(usage.check(613, &writer.source_map).is_empty());
+    let errors: Vec<String> = usage
+        .check(300, &writer.source_map)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        errors,
+        ["Sys.vm:25: error: the program takes 613 ROM words, more than the limit of 300, and `call Sys.add12 1` is the first command written past it"]
+    );
+    // A limit inside the bootstrap code has no VM line.
+    let errors = usage.check(10, &writer.source_map);
+    assert_eq!(
+        errors[0].to_string(),
+        "bootstrap: error: the program takes 613 ROM words, more than the limit of 10, and `bootstrap` is the first command written past it"
+    );
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::program::*;
+
+fn specialise() -> CodegenOptions {
+    CodegenOptions {
+        specialise: true,
+        ..Default::default()
+    }
+}
+
+fn all() -> CodegenOptions {
+    CodegenOptions {
+        tail_calls: true,
+        cache_top: true,
+        specialise: true,
+        ..Default::default()
+    }
+}
+
+/// Translates `program` into `output` and assembles the result.
+fn write(
+    program: &ProgramClass,
+    output: &str,
+    options: &CodegenOptions,
+    init: bool,
+) -> AssemblerClass {
+    let mut writer = CodeWriterClass::new(output.to_string());
+    writer.options = options.clone();
+    if init {
+        writer.write_init();
+    }
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    writer.flush();
+    AssemblerClass::new(&fs::read_to_string(output).unwrap())
+}
+
+#[test]
+fn fixtures_give_the_expected_ram() {
+    for options in [specialise(), all()] {
+        for name in FIXTURES {
+            assert_fixture_ram(name, translate_fixture("specialise", name, &options).rom);
+        }
+    }
+}
+
+#[test]
+fn saves_instructions() {
+    let (mut plain_total, mut specialised_total) = (0, 0);
+    for name in FIXTURES {
+        let plain = translate_fixture("specialise", name, &CodegenOptions::default())
+            .rom
+            .len();
+        let specialised = translate_fixture("specialise", name, &specialise())
+            .rom
+            .len();
+        assert!(specialised <= plain, "{name}: {specialised} of {plain}");
+        plain_total += plain;
+        specialised_total += specialised;
+    }
+    assert!(specialised_total * 10 < plain_total * 9);
+}
+
+#[test]
+fn specialised_shapes() {
+    let dir = write_program(
+        "specialise_shapes",
+        &[(
+            "Main.vm",
+            "push constant 1\nneg\npop local 2\npush constant 0\nnot\npush local 2\neq\nif-goto SAME\npush constant 0\npop temp 0\nlabel SAME\n",
+        )],
+    );
+    let output = format!("{dir}/Out.asm");
+    let assembler = write(&ProgramClass::new(vec![dir]), &output, &specialise(), false);
+    // Two pushes of -1 (4 words each), `pop local 2` (7), `push local 2` (8),
+    // the fused `eq; if-goto` (8), `push constant 0` (4) and `pop temp 0` (6).
+    assert_eq!(assembler.rom.len(), 4 + 7 + 4 + 8 + 8 + 4 + 6);
+
+    let mut emulator = EmulatorClass::new(assembler.rom);
+    emulator.ram[0] = 300;
+    emulator.ram[1] = 300;
+    emulator.ram[5] = 99;
+    emulator.ram[302] = 0;
+    emulator.run(1_000);
+    assert!(emulator.is_halted());
+    assert_eq!(emulator.ram[302], -1);
+    assert_eq!(emulator.ram[5], 99, "the fused branch was not taken");
+    assert_eq!(emulator.ram[0], 300);
+
+    // No `CON_TRUE_n` label is left for the fused comparison.
+    let asm = fs::read_to_string(output).unwrap();
+    assert!(!asm.contains("CON_TRUE"));
+}
+
+#[test]
+fn random_programs_match_interpreter() {
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("specialise_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        for (options, output) in [(specialise(), "Specialised.asm"), (all(), "All.asm")] {
+            let mut harness = DifferentialClass::from_programs(
+                &program,
+                &program,
+                &options,
+                format!("{dir}/{output}"),
+            );
+            harness
+                .run(200_000)
+                .unwrap_or_else(|error| panic!("seed {seed} {output}: {error}"));
+        }
+    }
+}
@@ -0,0 +1,94 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::prelude::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::stack_checker::*;
+
+fn check(name: &str, files: &[(&str, &str)]) -> Vec<String> {
+    let dir = write_program(name, files);
+    let mut checker = StackCheckerClass::new(&ProgramClass::new(vec![dir]));
+    checker
+        .check()
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+}
+
+#[test]
+fn fixtures_and_random_programs_are_consistent() {
+    for name in ["BasicLoop", "FibonacciElement", "NestedCall", "StaticsTest"] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let mut checker = StackCheckerClass::new(&ProgramClass::new(vec![fixture]));
+        assert_eq!(checker.check(), Vec::<Diagnostic>::new(), "{name}");
+    }
+    for seed in 0..20 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        assert_eq!(
+            check(&format!("stack_random_{seed}"), &files),
+            Vec::<String>::new()
+        );
+    }
+}
+
+#[test]
+fn reports_underflow_and_empty_returns() {
+    let main = "function Main.f 0
+push constant 1
+add
+call Main.g 2
+return
+function Main.g 1
+pop local 0
+return";
+    assert_eq!(
+        check("stack_underflow", &[("Main.vm", main)]),
+        [
+            "Main.vm:3: error: `add` pops 2 value(s) but the stack holds 1",
+            "Main.vm:4: error: `call Main.g 2` pops 2 value(s) but the stack holds 1",
+            "Main.vm:7: error: `pop local 0` pops 1 value(s) but the stack holds 0",
+            "Main.vm:8: error: `return` without a value on the stack",
+        ]
+    );
+}
+
+#[test]
+fn reports_inconsistent_heights_at_joins() {
+    let main = "function Main.f 0
+push argument 0
+if-goto ONE
+push constant 1
+push constant 2
+goto DONE
+label ONE
+push constant 3
+label DONE
+add
+return";
+    let checker_output = check("stack_join", &[("Main.vm", main)]);
+    assert_eq!(
+        checker_output,
+        ["Main.vm:9: error: inconsistent stack height: 2 on one path and 1 on another"]
+    );
+}
+
+#[test]
+fn records_the_height_before_each_instruction() {
+    let dir = write_program(
+        "stack_depths",
+        &[(
+            "Main.vm",
+            "push constant 1\npush constant 2\nlt\nif-goto END\nlabel END",
+        )],
+    );
+    let mut checker = StackCheckerClass::new(&ProgramClass::new(vec![dir]));
+    assert!(checker.check().is_empty());
+    assert_eq!(
+        checker.depths,
+        [Some(0), Some(1), Some(2), Some(1), Some(0)]
+    );
+}
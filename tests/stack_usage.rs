@@ -0,0 +1,123 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::call_graph::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::stack_usage::*;
+
+/// Runs the translated program and returns the highest value of `SP` above 256.
+fn peak_usage(name: &str, program: &ProgramClass) -> usize {
+    let output = scratch_dir(&format!("stack_usage_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    let mut emulator = EmulatorClass::new(assembler.rom);
+
+    let mut peak = 0;
+    while emulator.cycles < 1_000_000 && !emulator.is_halted() {
+        emulator.step();
+        peak = peak.max(emulator.ram[0] as usize);
+    }
+    peak - 256
+}
+
+#[test]
+fn bounds_the_emulated_stack() {
+    for name in ["NestedCall", "StaticsTest"] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let program = ProgramClass::new(vec![fixture]);
+        let bound = StackUsageClass::new(&program).bound(ENTRY_POINT);
+        // Without branches the worst case is what the program uses.
+        assert_eq!(bound, Some(peak_usage(name, &program)), "{name}");
+    }
+    for seed in 0..20 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let name = format!("random_{seed}");
+        let program =
+            ProgramClass::new(vec![write_program(&format!("stack_usage_{name}"), &files)]);
+        let bound = StackUsageClass::new(&program).bound(ENTRY_POINT).unwrap();
+        assert!(peak_usage(&name, &program) <= bound, "seed {seed}");
+    }
+}
+
+#[test]
+fn reports_recursive_cycles() {
+    let main = "function Main.a 0
+push constant 1
+call Main.b 1
+return
+function Main.b 0
+push argument 0
+call Main.a 1
+return
+function Main.c 0
+push constant 0
+call Main.c 1
+return
+function Main.d 2
+push constant 1
+push constant 2
+push constant 3
+add
+add
+return";
+    let sys = "function Sys.init 0
+call Main.d 0
+call Main.a 0
+label END
+goto END";
+    let program = ProgramClass::new(vec![write_program(
+        "stack_usage_cycles",
+        &[("Main.vm", main), ("Sys.vm", sys)],
+    )]);
+    let usage = StackUsageClass::new(&program);
+
+    assert_eq!(usage.cycles, [vec!["Main.a", "Main.b"], vec!["Main.c"]]);
+    assert_eq!(usage.frames["Main.d"], 5);
+    assert_eq!(usage.usage["Main.d"], Some(5));
+    assert_eq!(usage.usage["Main.a"], None);
+    assert_eq!(usage.usage["Sys.init"], None);
+    let report = usage.report(ENTRY_POINT);
+    assert!(report.contains(
+        "\nRecursive cycles (functions calling each other):\n  Main.a, Main.b\n  Main.c\n"
+    ));
+    assert!(report.ends_with("Worst-case stack usage from Sys.init: unbounded (recursive)\n"));
+}
+
+#[test]
+fn warns_when_the_stack_reaches_the_heap() {
+    // Every level pushes 5 words for the call and 40 for its local variables.
+    let main: String = (0..40)
+        .map(|i| format!("function Main.f{i} 40\ncall Main.f{} 0\nreturn\n", i + 1))
+        .chain(["function Main.f40 0\npush constant 0\nreturn\n".to_string()])
+        .collect();
+    let sys = "function Sys.init 0\ncall Main.f0 0\nlabel END\ngoto END";
+    let program = ProgramClass::new(vec![write_program(
+        "stack_usage_deep",
+        &[("Main.vm", &main), ("Sys.vm", sys)],
+    )]);
+    let usage = StackUsageClass::new(&program);
+
+    assert_eq!(usage.bound(ENTRY_POINT), Some(5 + 5 + 40 * 45 + 1));
+    let warnings: Vec<String> = usage
+        .check(ENTRY_POINT)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        warnings,
+        ["Sys.vm:1: warning: the stack can grow to 1811 words from Sys.init, more than the 1792 words between RAM 256 and the heap at 2048"]
+    );
+}
@@ -0,0 +1,117 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::statics::*;
+
+/// Translates the program like the translator's `main` and returns the variables allocated by
+/// the assembler.
+fn assembled_variables(name: &str, program: &ProgramClass) -> Vec<(String, usize)> {
+    let output = scratch_dir(&format!("statics_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let mut writer = CodeWriterClass::new(output.clone());
+    if program
+        .functions()
+        .iter()
+        .any(|function| function.name == "Sys.init")
+    {
+        writer.write_init();
+    }
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    let mut variables: Vec<(String, usize)> = assembler
+        .variables
+        .into_iter()
+        .map(|(symbol, address)| (symbol, address as usize))
+        .collect();
+    variables.sort_by_key(|(_, address)| *address);
+    variables
+}
+
+#[test]
+fn predicts_the_assembler_allocation() {
+    let fixture = format!("{}/tests/fixtures/StaticsTest", env!("CARGO_MANIFEST_DIR"));
+    let mut programs = vec![("fixture".to_string(), ProgramClass::new(vec![fixture]))];
+    for seed in 0..5 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("statics_random_{seed}"), &files);
+        programs.push((format!("random_{seed}"), ProgramClass::new(vec![dir])));
+    }
+    // Undeclared call targets and jump labels are variables too, and shift the statics after them.
+    let main = "function Main.f 0
+push static 0
+call Missing.g 0
+goto NOWHERE
+label LOOP
+push static 1
+return
+function Main.h 0
+if-goto LOOP
+push static 2
+call Missing.g 0
+return";
+    let dir = write_program("statics_undeclared", &[("Main.vm", main)]);
+    programs.push(("undeclared".to_string(), ProgramClass::new(vec![dir])));
+
+    for (name, program) in programs {
+        let statics = StaticsClass::new(&program);
+        let mut predicted: Vec<(String, usize)> = statics
+            .statics
+            .iter()
+            .chain(&statics.undeclared)
+            .map(|variable| (variable.symbol.clone(), variable.address))
+            .collect();
+        predicted.sort_by_key(|(_, address)| *address);
+        assert_eq!(predicted, assembled_variables(&name, &program), "{name}");
+        assert!(statics.check().is_empty());
+    }
+}
+
+#[test]
+fn counts_statics_per_module() {
+    let main = "push static 0\npop static 2\npush static 0\npop static 1";
+    let util = "call Missing.f 0\npush static 0";
+    let program = ProgramClass::new(vec![write_program(
+        "statics_modules",
+        &[("Main.vm", main), ("Util.vm", util)],
+    )]);
+    let statics = StaticsClass::new(&program);
+
+    assert_eq!(statics.modules["Main"], 3);
+    assert_eq!(statics.modules["Util"], 1);
+    let report = statics.report();
+    assert!(report.starts_with("Static allocation (RAM 16-255):\n    16  Main.0\n    17  Main.2\n"));
+    // The call to an undeclared function is a variable too.
+    assert!(report.contains("\n    19  Missing.f (undeclared)\n    20  Util.0\n"));
+    assert!(report.ends_with("(undeclared)                            1\nTotal                                   5 of 240\n"));
+}
+
+#[test]
+fn reports_overflow_into_the_stack() {
+    let main: String = (0..241).map(|i| format!("push static {i}\n")).collect();
+    let program = ProgramClass::new(vec![write_program(
+        "statics_overflow",
+        &[
+            ("Main.vm", &main),
+            ("Sys.vm", "function Sys.init 0\nlabel END\ngoto END"),
+        ],
+    )]);
+    let diagnostics: Vec<String> = StaticsClass::new(&program)
+        .check()
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect();
+    assert_eq!(
+        diagnostics,
+        ["Main.vm:241: error: static Main.240 is allocated at RAM 256, past the 240 addresses of the static region (241 statics in total)"]
+    );
+}
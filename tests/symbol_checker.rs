@@ -0,0 +1,81 @@
+mod common;
+
+use common::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::symbol_checker::*;
+
+fn check(name: &str, files: &[(&str, &str)], bootstrap: bool) -> Vec<String> {
+    let program = ProgramClass::new(vec![write_program(name, files)]);
+    SymbolCheckerClass::new(&program)
+        .check(bootstrap)
+        .iter()
+        .map(|diagnostic| diagnostic.to_string())
+        .collect()
+}
+
+#[test]
+fn fixtures_are_consistent() {
+    for name in ["FibonacciElement", "NestedCall", "StaticsTest"] {
+        let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+        let program = ProgramClass::new(vec![fixture]);
+        assert!(
+            SymbolCheckerClass::new(&program).check(true).is_empty(),
+            "{name}"
+        );
+    }
+}
+
+#[test]
+fn reports_undeclared_and_duplicate_functions() {
+    let sys = "function Sys.init 0
+call Main.main 0
+call Main.mian 0
+label END
+goto END";
+    let main = "function Main.main 0
+push constant 0
+return";
+    let other = "// Declares Main.main a second time.
+function Main.main 0
+push constant 1
+return";
+    assert_eq!(
+        check(
+            "symbols_undeclared",
+            &[("Sys.vm", sys), ("Main.vm", main), ("Other.vm", other)],
+            true
+        ),
+        [
+            "Other.vm:2: error: function Main.main is already declared at Main.vm:1",
+            "Sys.vm:3: error: call to undeclared function Main.mian",
+        ]
+    );
+}
+
+#[test]
+fn reports_argument_count_mismatches() {
+    let main = "function Main.main 0
+push constant 1
+call Main.f 1
+push constant 1
+push constant 2
+call Main.f 2
+return
+function Main.f 0
+push argument 0
+return";
+    assert_eq!(
+        check("symbols_arguments", &[("Main.vm", main)], false),
+        ["Main.vm:6: warning: Main.f is called with 2 argument(s) here but with 1 at Main.vm:3"]
+    );
+}
+
+#[test]
+fn requires_sys_init_when_bootstrapping() {
+    let main = "push constant 1\npush constant 2\nadd";
+    assert!(check("symbols_no_bootstrap", &[("Main.vm", main)], false).is_empty());
+    assert_eq!(
+        check("symbols_bootstrap", &[("Main.vm", main)], true),
+        ["bootstrap: error: the bootstrap code calls Sys.init, which is not declared"]
+    );
+}
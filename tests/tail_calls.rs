@@ -0,0 +1,268 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::differential::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::profiler::*;
+use virtual_machine_translator::utils::program::*;
+
+fn with_tail_calls() -> CodegenOptions {
+    CodegenOptions {
+        tail_calls: true,
+        ..Default::default()
+    }
+}
+
+/// Sums `n + (n - 1) + ... + 1` with a tail-recursive accumulator.
+const SUM: &str = "function Main.sum 0
+push argument 0
+push constant 0
+eq
+if-goto DONE
+push argument 0
+push constant 1
+sub
+push argument 1
+push argument 0
+add
+call Main.sum 2
+return
+label DONE
+push argument 1
+return";
+
+/// Translates the program and returns the writer and the assembled ROM.
+fn translate(
+    name: &str,
+    program: &ProgramClass,
+    options: &CodegenOptions,
+) -> (CodeWriterClass, Vec<u16>) {
+    let output = scratch_dir(&format!("tail_calls_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.options = options.clone();
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    writer.flush();
+    let assembler = AssemblerClass::new(&fs::read_to_string(output).unwrap());
+    (writer, assembler.rom)
+}
+
+/// Runs the ROM until it halts and returns the highest value of `SP` and the top of the stack.
+fn run(rom: Vec<u16>) -> (usize, i16) {
+    let mut emulator = EmulatorClass::new(rom);
+    let mut peak = 0;
+    while emulator.cycles < 5_000_000 && !emulator.is_halted() {
+        emulator.step();
+        peak = peak.max(emulator.ram[0] as usize);
+    }
+    assert!(emulator.is_halted());
+    (peak, emulator.ram[16])
+}
+
+fn sum_program(name: &str, n: usize) -> ProgramClass {
+    let sys = format!(
+        "function Sys.init 0
+push constant {n}
+push constant 0
+call Main.sum 2
+pop static 0
+label END
+goto END"
+    );
+    let dir = write_program(
+        &format!("tail_calls_{name}"),
+        &[("Main.vm", SUM), ("Sys.vm", &sys)],
+    );
+    ProgramClass::new(vec![dir])
+}
+
+#[test]
+fn tail_recursion_runs_in_constant_stack() {
+    let program = sum_program("sum", 1000);
+    // 1000 + 999 + ... + 1 = 500500 wraps to 500500 - 8 * 65536.
+    let expected = (500_500 - 8 * 65_536) as i16;
+
+    let (_, rom) = translate("sum_plain", &program, &CodegenOptions::default());
+    let (peak, result) = run(rom);
+    assert_eq!(result, expected);
+    assert!(peak > 256 + 7 * 1000);
+
+    let (_, rom) = translate("sum_tail", &program, &with_tail_calls());
+    let (peak, result) = run(rom);
+    assert_eq!(result, expected);
+    assert!(peak < 256 + 20, "{peak}");
+}
+
+#[test]
+fn tail_calls_match_interpreter() {
+    // Tail calls growing and shrinking the number of arguments, and mutual recursion.
+    let main = "function Main.even 1
+push argument 0
+pop local 0
+push local 0
+push constant 0
+eq
+if-goto YES
+push local 0
+push constant 1
+sub
+call Main.odd 1
+return
+label YES
+push constant 0
+not
+return
+function Main.odd 0
+push argument 0
+push constant 0
+eq
+if-goto NO
+push argument 0
+push constant 1
+sub
+call Main.even 1
+return
+label NO
+push constant 0
+return
+function Main.spread 2
+push argument 0
+push argument 0
+push constant 1
+add
+push argument 0
+push constant 2
+add
+call Main.collect 3
+return
+function Main.collect 0
+push argument 0
+push argument 1
+push argument 2
+add
+add
+call Main.single 1
+return
+function Main.single 0
+push argument 0
+push constant 100
+add
+return";
+    let sys = "function Sys.init 0
+push constant 7
+call Main.even 1
+pop static 0
+push constant 10
+call Main.odd 1
+pop static 1
+push constant 5
+call Main.spread 1
+pop static 2
+label END
+goto END";
+    let dir = write_program("tail_calls_mutual", &[("Main.vm", main), ("Sys.vm", sys)]);
+    let program = ProgramClass::new(vec![dir.clone()]);
+    let mut harness = DifferentialClass::from_programs(
+        &program,
+        &program,
+        &with_tail_calls(),
+        format!("{dir}/Out.asm"),
+    );
+    // Only the returns of Sys.init's callees reach a `.ret.` label.
+    assert_eq!(harness.run(10_000), Ok(3));
+    let statics = &harness.interpreter.statics;
+    assert_eq!(
+        [statics["Sys.0"], statics["Sys.1"], statics["Sys.2"]],
+        [0, 0, 118]
+    );
+
+    let program = sum_program("sum_differential", 300);
+    let mut harness = DifferentialClass::from_programs(
+        &program,
+        &program,
+        &with_tail_calls(),
+        format!("{dir}/Sum.asm"),
+    );
+    assert_eq!(harness.run(100_000), Ok(1));
+}
+
+#[test]
+fn random_programs_with_tail_calls_match_interpreter() {
+    let mut tail_calls = 0;
+    for seed in 0..60 {
+        let files = Generator::new(seed).generate();
+        let files: Vec<(&str, &str)> = files
+            .iter()
+            .map(|(name, source)| (name.as_str(), source.as_str()))
+            .collect();
+        let dir = write_program(&format!("tail_calls_random_{seed}"), &files);
+        let program = ProgramClass::new(vec![dir.clone()]);
+        let (writer, _) = translate(
+            &format!("random_{seed}_writer"),
+            &program,
+            &with_tail_calls(),
+        );
+        tail_calls += writer
+            .source_map
+            .iter()
+            .filter(|entry| entry.command == "return" && entry.length == 0)
+            .count();
+
+        let mut harness = DifferentialClass::from_programs(
+            &program,
+            &program,
+            &with_tail_calls(),
+            format!("{dir}/Out.asm"),
+        );
+        harness
+            .run(200_000)
+            .unwrap_or_else(|error| panic!("seed {seed}: {error}"));
+    }
+    assert!(tail_calls > 5, "{tail_calls}");
+}
+
+#[test]
+fn other_calls_are_unchanged() {
+    let fixture = format!(
+        "{}/tests/fixtures/FibonacciElement",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let program = ProgramClass::new(vec![fixture]);
+    let (_, plain) = translate("fibonacci_plain", &program, &CodegenOptions::default());
+    let (_, tail) = translate("fibonacci_tail", &program, &with_tail_calls());
+    assert_eq!(plain, tail);
+
+    // A call held back at the end of the program is still written.
+    let dir = write_program(
+        "tail_calls_flush",
+        &[("Main.vm", "push constant 1\ncall Main.f 1\n")],
+    );
+    let program = ProgramClass::new(vec![dir]);
+    let (writer, _) = translate("flush", &program, &with_tail_calls());
+    assert_eq!(writer.source_map.last().unwrap().command, "call Main.f 1");
+}
+
+#[test]
+fn profiler_leaves_the_function_making_a_tail_call() {
+    let program = sum_program("profile", 50);
+    let (writer, rom) = translate("profile", &program, &with_tail_calls());
+    let mut profiler = ProfilerClass::new(EmulatorClass::new(rom), writer.source_map);
+    profiler.run(1_000_000);
+
+    assert_eq!(profiler.functions["Main.sum"].calls, 51);
+    let folded = profiler.folded();
+    let stacks: Vec<&str> = folded
+        .lines()
+        .map(|line| line.rsplit_once(' ').unwrap().0)
+        .collect();
+    assert!(stacks.contains(&"<top level>;Sys.init;Main.sum"));
+    assert!(stacks
+        .iter()
+        .all(|stack| !stack.contains("Main.sum;Main.sum")));
+}
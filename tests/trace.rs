@@ -0,0 +1,68 @@
+mod common;
+
+use common::*;
+use std::fs;
+use virtual_machine_translator::utils::assembler::*;
+use virtual_machine_translator::utils::code_writer::*;
+use virtual_machine_translator::utils::emulator::*;
+use virtual_machine_translator::utils::program::*;
+use virtual_machine_translator::utils::trace::*;
+
+fn translate(name: &str) -> (AssemblerClass, CodeWriterClass) {
+    let fixture = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    let output = scratch_dir(&format!("trace_{name}")).join("Out.asm");
+    let output = output.to_string_lossy().to_string();
+
+    let program = ProgramClass::new(vec![fixture]);
+    let mut writer = CodeWriterClass::new(output.clone());
+    writer.write_init();
+    for instruction in &program.instructions {
+        writer.write_instruction(instruction);
+    }
+    (
+        AssemblerClass::new(&fs::read_to_string(output).unwrap()),
+        writer,
+    )
+}
+
+#[test]
+fn source_map_covers_every_instruction() {
+    let (assembler, writer) = translate("StaticsTest");
+
+    let mut address = 0;
+    for entry in &writer.source_map {
+        assert_eq!(entry.address, address, "{entry:?}");
+        address += entry.length;
+    }
+    assert_eq!(address, assembler.rom.len());
+
+    // The return address of a call is the end of the call command.
+    let call = writer
+        .source_map
+        .iter()
+        .find(|entry| entry.command == "call Class1.get 0")
+        .unwrap();
+    let label = assembler
+        .labels
+        .iter()
+        .find(|(_, address)| **address as usize == call.address + call.length)
+        .unwrap();
+    assert!(label.0.starts_with("Class1.get.ret."));
+}
+
+#[test]
+fn trace_prints_vm_commands_of_selected_functions() {
+    let (assembler, writer) = translate("NestedCall");
+    let mut tracer = TracerClass::new(EmulatorClass::new(assembler.rom), writer.source_map);
+    tracer.functions = vec!["Sys.add12".to_string()];
+
+    let mut output: Vec<u8> = Vec::new();
+    let traced = tracer.run(100_000, &mut output);
+    let output = String::from_utf8(output).unwrap();
+    let lines: Vec<&str> = output.lines().collect();
+
+    assert_eq!(traced, 8);
+    assert!(lines[0].starts_with("Sys.vm:40"), "{}", lines[0]);
+    assert!(lines[0].contains("push constant 4002"));
+    assert!(lines[7].contains("return") && lines[7].ends_with("top=135"));
+}